@@ -0,0 +1,313 @@
+use std;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+
+/// Where the player's animations are loaded from
+pub const ANIMATION_DIRECTORY: &str = "animations";
+
+
+/// The angles of every limb in a pose, in degrees counter-clockwise from facing forwards.
+///
+/// The first of each pair is the limb closest to the camera.
+#[derive(Copy, Clone)]
+pub struct Pose {
+    /// From the hip to the shoulder
+    pub torso: f64,
+
+    pub arms: [f64; 2],
+    pub forearms: [f64; 2],
+
+    pub thighs: [f64; 2],
+    pub shins: [f64; 2],
+}
+
+
+/// Poses at points along a timeline, which does not have to be time: the run cycle is played back
+/// by the distance travelled, for example
+#[derive(Clone)]
+pub struct Animation {
+    // Keyframes sorted by their position on the timeline
+    keys: Vec<(f64, Pose)>,
+
+    // Length of the timeline if the animation repeats
+    loop_length: Option<f64>,
+}
+
+
+/// Every animation the player uses
+#[derive(Clone)]
+pub struct AnimationSet {
+    pub run: Animation,
+    pub fall: Animation,
+    pub wall: Animation,
+    pub crouch: Animation,
+    pub slide: Animation,
+}
+
+
+/// The animation files, with the version the game ships with for when a file is missing
+const ANIMATION_FILES: [(&str, &str); 5] = [
+    ("run.anim", include_str!("../animations/run.anim")),
+    ("fall.anim", include_str!("../animations/fall.anim")),
+    ("wall.anim", include_str!("../animations/wall.anim")),
+    ("crouch.anim", include_str!("../animations/crouch.anim")),
+    ("slide.anim", include_str!("../animations/slide.anim")),
+];
+
+
+impl Pose {
+    /// Returns the pose part of the way to another pose
+    pub fn lerp(&self, other: &Pose, t: f64) -> Pose {
+        let lerp = |a: f64, b: f64| { a + (b - a) * t };
+        let lerp_pair = |a: [f64; 2], b: [f64; 2]| { [lerp(a[0], b[0]), lerp(a[1], b[1])] };
+
+        Pose {
+            torso: lerp(self.torso, other.torso),
+            arms: lerp_pair(self.arms, other.arms),
+            forearms: lerp_pair(self.forearms, other.forearms),
+            thighs: lerp_pair(self.thighs, other.thighs),
+            shins: lerp_pair(self.shins, other.shins),
+        }
+    }
+}
+
+
+impl Animation {
+    /// Parses an animation file, lines starting with '#' are comments
+    fn from_str(text: &str) -> Option<Animation> {
+        let mut animation = Animation {
+            keys: Vec::new(),
+            loop_length: None,
+        };
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+
+            if let Some(word) = words.next() {
+                match word {
+                    word if word.starts_with('#') => {}
+
+
+                    // The length of the timeline, after which the animation starts over
+                    "LOOP" => {
+                        if let Some(length) = words.next().and_then(|l| l.parse::<f64>().ok()).filter(|l| l.is_finite()) {
+                            animation.loop_length = Some(length);
+                        } else {
+                            println!("LOOP: expected a number!");
+                            return None;
+                        }
+                    }
+
+
+                    // A pose at a point on the timeline, followed by the angles of every limb
+                    "KEY" => {
+                        // Keyframes are sorted by time, which needs every time to be comparable
+                        let time = if let Some(time) = words.next().and_then(|t| t.parse::<f64>().ok()).filter(|t| t.is_finite()) {
+                            time
+                        } else {
+                            println!("KEY: expected a number!");
+                            return None;
+                        };
+
+                        let mut pose = Pose {
+                            torso: 0.0,
+                            arms: [0.0; 2],
+                            forearms: [0.0; 2],
+                            thighs: [0.0; 2],
+                            shins: [0.0; 2],
+                        };
+
+                        while let Some(limb) = words.next() {
+                            let count = if limb == "TORSO" { 1 } else { 2 };
+
+                            let mut angles = [0.0; 2];
+                            for angle in angles.iter_mut().take(count) {
+                                if let Some(value) = words.next().and_then(|a| a.parse::<f64>().ok()) {
+                                    *angle = value;
+                                } else {
+                                    println!("KEY: expected {} angle(s) for {}!", count, limb);
+                                    return None;
+                                }
+                            }
+
+                            match limb {
+                                "TORSO" => pose.torso = angles[0],
+                                "ARMS" => pose.arms = angles,
+                                "FOREARMS" => pose.forearms = angles,
+                                "THIGHS" => pose.thighs = angles,
+                                "SHINS" => pose.shins = angles,
+                                limb => {
+                                    println!("KEY: unknown limb '{}'", limb);
+                                    return None;
+                                }
+                            }
+                        }
+
+                        animation.keys.push((time, pose));
+                    }
+
+                    word => {
+                        println!("Failed to load animation: invalid command '{}'", word);
+                        return None;
+                    }
+                }
+            }
+        }
+
+        if animation.keys.is_empty() {
+            println!("Failed to load animation: no keyframes");
+            return None;
+        }
+
+        animation.keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        Some(animation)
+    }
+
+
+    /// Returns the pose at a point on the timeline, blended between the keyframes around it
+    pub fn sample(&self, time: f64) -> Pose {
+        let first = self.keys[0];
+        let last = self.keys[self.keys.len() - 1];
+
+        let time = match self.loop_length {
+            Some(length) if length > 0.0 => {
+                let offset = time - first.0;
+                first.0 + offset - (offset / length).floor() * length
+            }
+            _ => time,
+        };
+
+        for pair in self.keys.windows(2) {
+            let (start, ref from) = pair[0];
+            let (end, ref to) = pair[1];
+
+            if time >= start && time < end {
+                return from.lerp(to, (time - start) / (end - start));
+            }
+        }
+
+        match self.loop_length {
+            // Wrap around from the last keyframe to the first
+            Some(length) if length > 0.0 && time >= last.0 => {
+                let end = first.0 + length;
+                if end > last.0 {
+                    last.1.lerp(&first.1, (time - last.0) / (end - last.0))
+                } else {
+                    last.1
+                }
+            }
+
+            _ => if time < first.0 { first.1 } else { last.1 },
+        }
+    }
+}
+
+
+impl AnimationSet {
+    /// The animations the game was designed with
+    pub fn new() -> AnimationSet {
+        AnimationSet::from_sources(|_, source| Animation::from_str(source))
+            .expect("Built in animations are invalid")
+    }
+
+
+    /// Open every animation in a directory, animations missing from the directory use the built in
+    /// version
+    pub fn open<P: AsRef<Path>>(directory: P) -> Option<AnimationSet> {
+        AnimationSet::from_sources(|name, source| {
+            let path = directory.as_ref().join(name);
+
+            if let Ok(mut file) = File::open(&path) {
+                use std::io::Read;
+
+                let mut string = String::new();
+                if file.read_to_string(&mut string).is_err() {
+                    return None;
+                }
+
+                let animation = Animation::from_str(&string);
+                if animation.is_none() {
+                    println!("Failed to load {}", path.display());
+                }
+                animation
+            } else {
+                Animation::from_str(source)
+            }
+        })
+    }
+
+
+    /// Loads every animation file, given its name and built in contents
+    fn from_sources<F>(mut load: F) -> Option<AnimationSet>
+        where F: FnMut(&str, &str) -> Option<Animation>
+    {
+        let mut animations = Vec::new();
+        for &(name, source) in ANIMATION_FILES.iter() {
+            animations.push(load(name, source)?);
+        }
+
+        let mut animations = animations.into_iter();
+        let mut next = || animations.next().unwrap();
+
+        Some(AnimationSet {
+            run: next(),
+            fall: next(),
+            wall: next(),
+            crouch: next(),
+            slide: next(),
+        })
+    }
+}
+
+
+/// Keeps the animations up to date with the files they were loaded from
+pub struct AnimationWatcher {
+    directory: PathBuf,
+    modified: Vec<Option<SystemTime>>,
+
+    animations: AnimationSet,
+}
+
+
+impl AnimationWatcher {
+    /// Load the animations in a directory, using the built in animations until they can be loaded
+    pub fn new<P: AsRef<Path>>(directory: P) -> AnimationWatcher {
+        let mut watcher = AnimationWatcher {
+            directory: directory.as_ref().to_path_buf(),
+            modified: Vec::new(),
+            animations: AnimationSet::new(),
+        };
+
+        watcher.poll();
+        watcher
+    }
+
+
+    /// Reloads the animations if any of the files changed since they were last loaded, returns true
+    /// if they were reloaded
+    pub fn poll(&mut self) -> bool {
+        let modified: Vec<Option<SystemTime>> = ANIMATION_FILES.iter().map(|&(name, _)| {
+            std::fs::metadata(self.directory.join(name)).and_then(|metadata| metadata.modified()).ok()
+        }).collect();
+
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        if let Some(animations) = AnimationSet::open(&self.directory) {
+            self.animations = animations;
+            true
+        } else {
+            false
+        }
+    }
+
+
+    pub fn get_animations(&self) -> &AnimationSet {
+        &self.animations
+    }
+}
@@ -0,0 +1,198 @@
+use trap::Vector2;
+
+use rax::collision::AABB;
+
+
+/// Half the size of the box the player can move around in without the camera following, in pixels
+const DEAD_ZONE_WIDTH: f64 = 48.0;
+const DEAD_ZONE_HEIGHT: f64 = 160.0;
+
+/// How far ahead of the player the camera looks when running at full speed, in pixels
+const LOOK_AHEAD_DISTANCE: f64 = 120.0;
+
+/// The horizontal speed at which the camera looks ahead the full distance, in pixels per second
+const LOOK_AHEAD_SPEED: f64 = 250.0;
+
+/// How quickly the camera catches up with where it wants to be, and how quickly it turns around
+const FOLLOW_RATE: f64 = 4.0;
+const LOOK_AHEAD_RATE: f64 = 2.0;
+
+/// How far the view is moved and turned by a shake at full trauma, in pixels and radians
+const MAX_SHAKE_OFFSET: f64 = 24.0;
+const MAX_SHAKE_ANGLE: f64 = 0.05;
+
+/// How much trauma is lost every second
+const TRAUMA_DECAY: f64 = 1.5;
+
+/// How quickly a shake moves back and forth
+const SHAKE_FREQUENCY: f64 = 25.0;
+
+
+/// Follows the player through a level, and shakes when something violent happens
+pub struct Camera {
+    // The point the camera looks at, without shake
+    center: Vector2,
+
+    // The point that is followed, moved only when the player leaves the dead zone around it
+    focus: Vector2,
+
+    // Current offset in the direction the player is moving
+    look_ahead: f64,
+
+    // Size of the view, in pixels
+    view_size: Vector2,
+
+    // The area of the level the view is kept inside
+    bounds: Option<AABB>,
+
+    // Strength of the shake, from 0 to 1
+    trauma: f64,
+
+    // Time spent shaking, used to move the shake along
+    shake_time: f64,
+
+    // Center and rotation, including shake, before the last update
+    previous_center: Vector2,
+    previous_rotation: f64,
+}
+
+
+impl Camera {
+    /// Create a camera looking at a point
+    pub fn new(center: Vector2) -> Camera {
+        Camera {
+            center,
+            focus: center,
+
+            look_ahead: 0.0,
+
+            view_size: Vector2::new(1.0, 1.0),
+            bounds: None,
+
+            trauma: 0.0,
+            shake_time: 0.0,
+
+            previous_center: center,
+            previous_rotation: 0.0,
+        }
+    }
+
+
+    /// Returns the point the view should be centered on, including shake, a fraction of the way
+    /// from before the last update to after it
+    pub fn get_center(&self, alpha: f64) -> Vector2 {
+        self.previous_center + (self.get_shaken_center() - self.previous_center) * alpha
+    }
+
+
+    /// Returns how far the view should be rotated by the shake, in radians, a fraction of the way
+    /// from before the last update to after it
+    pub fn get_rotation(&self, alpha: f64) -> f64 {
+        self.previous_rotation + (self.get_shake_rotation() - self.previous_rotation) * alpha
+    }
+
+
+    /// Set the size of the view, in pixels
+    pub fn set_view_size(&mut self, width: f64, height: f64) {
+        self.view_size = Vector2::new(width, height);
+    }
+
+
+    /// Set the area the view is kept inside, or None to let it go anywhere
+    pub fn set_bounds(&mut self, bounds: Option<AABB>) {
+        self.bounds = bounds;
+    }
+
+
+    /// Looks at a point right away, without following it there
+    pub fn jump_to(&mut self, center: Vector2) {
+        self.focus = center;
+        self.look_ahead = 0.0;
+        self.center = self.clamp_to_bounds(center);
+
+        self.previous_center = self.get_shaken_center();
+        self.previous_rotation = self.get_shake_rotation();
+    }
+
+
+    /// Makes the camera shake, trauma adds up to at most 1
+    pub fn add_trauma(&mut self, amount: f64) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+
+    /// Follows a target moving with a velocity. The camera only moves up and down to the target
+    /// while it stands on the ground, so that jumps do not move the view.
+    pub fn update(&mut self, dt: f64, target: Vector2, velocity: Vector2, grounded: bool) {
+        self.previous_center = self.get_shaken_center();
+        self.previous_rotation = self.get_shake_rotation();
+
+        // Horizontally the focus is dragged along by the edge of the dead zone
+        if target.x > self.focus.x + DEAD_ZONE_WIDTH {
+            self.focus.x = target.x - DEAD_ZONE_WIDTH;
+        } else if target.x < self.focus.x - DEAD_ZONE_WIDTH {
+            self.focus.x = target.x + DEAD_ZONE_WIDTH;
+        }
+
+        // Vertically the focus stays at the last ground height, unless the target leaves the
+        // dead zone by falling far or climbing high
+        if grounded {
+            self.focus.y = target.y;
+        } else if target.y > self.focus.y + DEAD_ZONE_HEIGHT {
+            self.focus.y = target.y - DEAD_ZONE_HEIGHT;
+        } else if target.y < self.focus.y - DEAD_ZONE_HEIGHT {
+            self.focus.y = target.y + DEAD_ZONE_HEIGHT;
+        }
+
+        let speed = (velocity.x / LOOK_AHEAD_SPEED).max(-1.0).min(1.0);
+        self.look_ahead += (speed * LOOK_AHEAD_DISTANCE - self.look_ahead) * (dt * LOOK_AHEAD_RATE).min(1.0);
+
+        let goal = self.clamp_to_bounds(self.focus + Vector2::new(self.look_ahead, 0.0));
+        self.center += (goal - self.center) * (dt * FOLLOW_RATE).min(1.0);
+
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        self.shake_time = if self.trauma > 0.0 { self.shake_time + dt } else { 0.0 };
+    }
+
+
+    /// Moves a center so that the view stays inside the bounds, a view larger than the bounds is
+    /// centered on them
+    fn clamp_to_bounds(&self, center: Vector2) -> Vector2 {
+        if let Some(ref bounds) = self.bounds {
+            let half = self.view_size / 2.0;
+
+            let clamp = |value: f64, min: f64, max: f64| {
+                if min > max { (min + max) / 2.0 } else { value.max(min).min(max) }
+            };
+
+            Vector2::new(
+                clamp(center.x, bounds.left + half.x, bounds.right - half.x),
+                clamp(center.y, bounds.top + half.y, bounds.bottom - half.y),
+            )
+        } else {
+            center
+        }
+    }
+
+
+    /// Returns the center moved by the shake. Squaring the trauma makes small shakes subtle and
+    /// large ones violent.
+    fn get_shaken_center(&self) -> Vector2 {
+        let strength = self.trauma * self.trauma * MAX_SHAKE_OFFSET;
+        let t = self.shake_time * SHAKE_FREQUENCY;
+
+        self.center + Vector2::new(noise(t, 0.0), noise(t, 10.0)) * strength
+    }
+
+
+    /// Returns how far the view is rotated by the shake
+    fn get_shake_rotation(&self) -> f64 {
+        self.trauma * self.trauma * MAX_SHAKE_ANGLE * noise(self.shake_time * SHAKE_FREQUENCY, 20.0)
+    }
+}
+
+
+/// Smooth noise between -1 and 1, a different seed gives a different wave
+fn noise(t: f64, seed: f64) -> f64 {
+    ((t + seed).sin() + (2.3 * t + 1.7 * seed).sin() * 0.5 + (5.1 * t + 0.3 * seed).sin() * 0.25) / 1.75
+}
@@ -53,9 +53,9 @@ impl CollisionTests {
 }
 
 impl rax::Game for CollisionTests {
-    fn update(&mut self, dt: f64) {}
+    fn update(&mut self, dt: f64, _input: &rax::input::Input) {}
 
-    fn render(&mut self, renderer: &mut rax::Renderer) {
+    fn render(&mut self, renderer: &mut rax::Renderer, _alpha: f64) {
         renderer.clear(0.01, 0.01, 0.01);
 
         renderer.color = [0.0, 0.0, 1.0, 1.0];
@@ -172,6 +172,17 @@ impl<'a> Collide<ConvexHull> for &'a [ConvexHull] {
             None
         }
     }
+
+
+    fn sweep(&self, other: &ConvexHull, motion: Vector2) -> Option<(f64, Vector2)> {
+        self.iter().filter_map(|c| c.sweep(other, motion))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+    }
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        self.iter().filter_map(|c| c.raycast(origin, direction))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+    }
     /*fn overlap(&self, other: &ConvexHull) -> Option<(f64, Vector2)> {
             let bounding_box = other.bounding_box();
             let mut overlaps = Vec::new();
@@ -0,0 +1,138 @@
+use rax::actions::ActionMap;
+use rax::input::Input;
+
+use player::PlayerCommand;
+
+
+/// Where the key bindings are loaded from
+pub const BINDINGS_PATH: &str = "config/bindings.cfg";
+
+/// The bindings the game ships with, for when the file is missing
+const DEFAULT_BINDINGS: &str = include_str!("../config/bindings.cfg");
+
+
+/// The actions a player controls their character with
+#[derive(Copy, Clone)]
+pub struct Controls {
+    pub left: &'static str,
+    pub right: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+
+    pub jump: &'static str,
+    pub dash: &'static str,
+}
+
+
+/// Controls for up to four players sharing a keyboard, the first is used when playing alone
+pub const PRESETS: [Controls; 4] = [
+    Controls {
+        left: "P1_LEFT",
+        right: "P1_RIGHT",
+        up: "P1_UP",
+        down: "P1_DOWN",
+        jump: "P1_JUMP",
+        dash: "P1_DASH",
+    },
+    Controls {
+        left: "P2_LEFT",
+        right: "P2_RIGHT",
+        up: "P2_UP",
+        down: "P2_DOWN",
+        jump: "P2_JUMP",
+        dash: "P2_DASH",
+    },
+    Controls {
+        left: "P3_LEFT",
+        right: "P3_RIGHT",
+        up: "P3_UP",
+        down: "P3_DOWN",
+        jump: "P3_JUMP",
+        dash: "P3_DASH",
+    },
+    Controls {
+        left: "P4_LEFT",
+        right: "P4_RIGHT",
+        up: "P4_UP",
+        down: "P4_DOWN",
+        jump: "P4_JUMP",
+        dash: "P4_DASH",
+    },
+];
+
+
+/// Load the key bindings, using the built in bindings if the file can not be loaded
+pub fn load_bindings() -> ActionMap {
+    ActionMap::open(BINDINGS_PATH).unwrap_or_else(|| {
+        println!("Failed to load {}, using default bindings", BINDINGS_PATH);
+        ActionMap::from_str(DEFAULT_BINDINGS).expect("Built in bindings are invalid")
+    })
+}
+
+
+impl Controls {
+    /// Returns the commands for the actions that are held down during a tick
+    pub fn held_commands(&self, actions: &ActionMap, input: &Input) -> Vec<PlayerCommand> {
+        let mut commands = Vec::new();
+
+        let left = actions.is_active(self.left, input);
+        let right = actions.is_active(self.right, input);
+        let down = actions.is_active(self.down, input);
+
+        if left { commands.push(PlayerCommand::MoveLeft); }
+        if right { commands.push(PlayerCommand::MoveRight); }
+
+        if down && (left || right) {
+            commands.push(PlayerCommand::Slide);
+        }
+
+        if actions.is_active(self.up, input) { commands.push(PlayerCommand::AimUp); }
+        if down {
+            commands.push(PlayerCommand::AimDown);
+            commands.push(PlayerCommand::Crouch);
+        }
+
+        commands
+    }
+
+
+    /// Returns the command for an action that was just started
+    pub fn start_command(&self, action: &str, actions: &ActionMap, input: &Input) -> Option<PlayerCommand> {
+        if action == self.jump {
+            Some(PlayerCommand::Jump)
+        } else if action == self.dash {
+            Some(PlayerCommand::Dash)
+        } else if action == self.down && !actions.is_active(self.left, input) && !actions.is_active(self.right, input) {
+            Some(PlayerCommand::Drop)
+        } else {
+            None
+        }
+    }
+
+
+    /// Returns the command for an action that was just stopped
+    pub fn stop_command(&self, action: &str) -> Option<PlayerCommand> {
+        if action == self.jump {
+            Some(PlayerCommand::StopJump)
+        } else {
+            None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_bindings_cover_every_preset() {
+        let actions = ActionMap::from_str(DEFAULT_BINDINGS).expect("Built in bindings are invalid");
+
+        for controls in PRESETS.iter() {
+            for action in [controls.left, controls.right, controls.up, controls.down, controls.jump, controls.dash].iter() {
+                assert!(!actions.get_bindings(action).is_empty(), "{} is not bound", action);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::hash::Hasher;
+
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+
+/// The 64-bit FNV-1a hash
+///
+/// Unlike the standard library's hasher its output never changes, so it can be used for hashes
+/// that are saved to disk. Integers are always written in little endian. Only integers and bytes
+/// should be written to it, since how other types hash themselves may change between versions
+/// of Rust.
+pub struct Fnv1a {
+    state: u64,
+}
+
+
+impl Fnv1a {
+    pub fn new() -> Fnv1a {
+        Fnv1a { state: OFFSET_BASIS }
+    }
+}
+
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u64;
+            self.state = self.state.wrapping_mul(PRIME);
+        }
+    }
+
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&[value as u8, (value >> 8) as u8]);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write_u16(value as u16);
+        self.write_u16((value >> 16) as u16);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write_u32(value as u32);
+        self.write_u32((value >> 32) as u32);
+    }
+
+    fn write_i16(&mut self, value: i16) {
+        self.write_u16(value as u16);
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write_u32(value as u32);
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.write_u64(value as u64);
+    }
+
+    // Sizes are written as 64 bits, to hash the same on every platform
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.write_i64(value as i64);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn integers_are_little_endian() {
+        let mut hasher = Fnv1a::new();
+        hasher.write_u32(0x6162_6364);
+
+        assert_eq!(hasher.finish(), hash(b"dcba"));
+    }
+
+    #[test]
+    fn sizes_are_64_bits() {
+        let mut sized = Fnv1a::new();
+        sized.write_usize(7);
+
+        let mut wide = Fnv1a::new();
+        wide.write_u64(7);
+
+        assert_eq!(sized.finish(), wide.finish());
+    }
+}
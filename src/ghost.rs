@@ -0,0 +1,304 @@
+use std;
+use std::fs::File;
+use std::path::Path;
+
+use trap::Vector2;
+
+use rax::Renderer;
+
+use player::{Player, RagDoll};
+
+
+/// Identifies ghost files, followed by the format version
+const MAGIC: &[u8; 4] = b"RPGH";
+const FORMAT_VERSION: u8 = 2;
+
+/// Size of the header: magic, version, level hash, movement hash and number of ticks
+const HEADER_SIZE: usize = 4 + 1 + 8 + 8 + 4;
+
+/// Positions are stored in fixed point, in steps of 1/16th of a pixel
+const PRECISION: f64 = 16.0;
+
+/// Size of a single tick in the file: the hip as two i32 followed by the other points as i16
+/// offsets from the hip
+const FRAME_SIZE: usize = 2 * 4 + (RagDoll::POINT_COUNT - 1) * 2 * 2;
+
+
+/// The position and pose of a player during every tick of a run
+#[derive(Clone)]
+pub struct Ghost {
+    level_hash: u64,
+    movement_hash: u64,
+
+    frames: Vec<RagDoll>,
+}
+
+
+impl Ghost {
+    /// Start recording a run through a level, played with movement parameters that have a hash
+    pub fn new(level_hash: u64, movement_hash: u64) -> Ghost {
+        Ghost {
+            level_hash,
+            movement_hash,
+            frames: Vec::new(),
+        }
+    }
+
+
+    /// Open a ghost from disk
+    pub fn open<P: AsRef<Path>>(path: P) -> Option<Ghost> {
+        if let Ok(mut file) = File::open(path) {
+            use std::io::Read;
+
+            let mut bytes = Vec::new();
+            if file.read_to_end(&mut bytes).is_err() {
+                return None;
+            }
+
+            Ghost::from_bytes(&bytes)
+        } else {
+            None
+        }
+    }
+
+
+    /// Save a ghost to disk
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+        file.write_all(&self.to_bytes())
+    }
+
+
+    /// Returns the contents of a ghost file
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.frames.len() * FRAME_SIZE);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(FORMAT_VERSION);
+        write_u64(&mut bytes, self.level_hash);
+        write_u64(&mut bytes, self.movement_hash);
+        write_u32(&mut bytes, self.frames.len() as u32);
+
+        for pose in self.frames.iter() {
+            let points = pose.get_points();
+            let hip = (fixed(points[0].x), fixed(points[0].y));
+
+            write_u32(&mut bytes, hip.0 as u32);
+            write_u32(&mut bytes, hip.1 as u32);
+
+            for point in points[1..].iter() {
+                write_u16(&mut bytes, (fixed(point.x) - hip.0) as i16 as u16);
+                write_u16(&mut bytes, (fixed(point.y) - hip.1) as i16 as u16);
+            }
+        }
+
+        bytes
+    }
+
+
+    /// Parses the contents of a ghost file
+    fn from_bytes(bytes: &[u8]) -> Option<Ghost> {
+        if bytes.len() < 5 || &bytes[0..4] != MAGIC {
+            println!("Failed to load ghost: not a ghost file");
+            return None;
+        }
+
+        if bytes[4] != FORMAT_VERSION {
+            println!("Failed to load ghost: unsupported version {}", bytes[4]);
+            return None;
+        }
+
+        if bytes.len() < HEADER_SIZE {
+            println!("Failed to load ghost: file is too short");
+            return None;
+        }
+
+        let level_hash = read_u64(&bytes[5..]);
+        let movement_hash = read_u64(&bytes[13..]);
+        let count = read_u32(&bytes[21..]) as usize;
+
+        let data = &bytes[HEADER_SIZE..];
+        if data.len() != count * FRAME_SIZE {
+            println!("Failed to load ghost: expected {} ticks", count);
+            return None;
+        }
+
+        let frames = data.chunks(FRAME_SIZE).map(|frame| {
+            let hip = (read_u32(frame) as i32, read_u32(&frame[4..]) as i32);
+
+            let mut points = [Vector2::new(0.0, 0.0); RagDoll::POINT_COUNT];
+            points[0] = Vector2::new(float(hip.0), float(hip.1));
+
+            for i in 1..RagDoll::POINT_COUNT {
+                let offset = 8 + (i - 1) * 4;
+                let x = read_u16(&frame[offset..]) as i16 as i32;
+                let y = read_u16(&frame[offset + 2..]) as i16 as i32;
+
+                points[i] = Vector2::new(float(hip.0 + x), float(hip.1 + y));
+            }
+
+            RagDoll::from_points(&points)
+        }).collect();
+
+        Some(Ghost {
+            level_hash,
+            movement_hash,
+            frames,
+        })
+    }
+
+
+    /// Adds the player's pose at the end of the next tick
+    pub fn record(&mut self, player: &Player) {
+        self.frames.push(player.get_pose().clone());
+    }
+
+
+    /// Draws the ghost as it was after a number of ticks
+    pub fn draw(&self, renderer: &mut Renderer, tick: u64, color: [f64; 4]) {
+        if self.frames.is_empty() {
+            return;
+        }
+
+        // The ghost stays where it finished once the run is over
+        let index = (tick.max(1) - 1).min(self.frames.len() as u64 - 1);
+        self.frames[index as usize].draw(renderer, color);
+    }
+
+
+    /// Returns the number of ticks the run took
+    pub fn len(&self) -> u64 {
+        self.frames.len() as u64
+    }
+
+
+    pub fn get_level_hash(&self) -> u64 {
+        self.level_hash
+    }
+
+    pub fn get_movement_hash(&self) -> u64 {
+        self.movement_hash
+    }
+}
+
+
+/// Converts a coordinate to fixed point
+fn fixed(value: f64) -> i32 {
+    (value * PRECISION).round() as i32
+}
+
+/// Converts a fixed point coordinate back
+fn float(value: i32) -> f64 {
+    value as f64 / PRECISION
+}
+
+
+// All numbers are stored in little endian
+
+fn write_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.push(value as u8);
+    bytes.push((value >> 8) as u8);
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    write_u16(bytes, value as u16);
+    write_u16(bytes, (value >> 16) as u16);
+}
+
+fn write_u64(bytes: &mut Vec<u8>, value: u64) {
+    write_u32(bytes, value as u32);
+    write_u32(bytes, (value >> 32) as u32);
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    bytes[0] as u16 | (bytes[1] as u16) << 8
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    read_u16(bytes) as u32 | (read_u16(&bytes[2..]) as u32) << 16
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    read_u32(bytes) as u64 | (read_u32(&bytes[4..]) as u64) << 32
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(offset: f64) -> RagDoll {
+        let mut points = [Vector2::new(0.0, 0.0); RagDoll::POINT_COUNT];
+        for (i, point) in points.iter_mut().enumerate() {
+            *point = Vector2::new(offset + i as f64 * 1.5, -offset - i as f64 * 0.25);
+        }
+
+        RagDoll::from_points(&points)
+    }
+
+    fn ghost() -> Ghost {
+        let mut ghost = Ghost::new(0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210);
+        ghost.frames.push(pose(0.0));
+        ghost.frames.push(pose(-250.5));
+        ghost.frames.push(pose(10_000.5));
+        ghost
+    }
+
+    #[test]
+    fn round_trip() {
+        let ghost = ghost();
+        let bytes = ghost.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE + 3 * FRAME_SIZE);
+
+        let loaded = Ghost::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.get_level_hash(), 0x0123_4567_89ab_cdef);
+        assert_eq!(loaded.get_movement_hash(), 0xfedc_ba98_7654_3210);
+        assert_eq!(loaded.len(), 3);
+
+        for (frame, expected) in loaded.frames.iter().zip(ghost.frames.iter()) {
+            for (point, expected) in frame.get_points().iter().zip(expected.get_points().iter()) {
+                assert_eq!((point.x, point.y), (expected.x, expected.y));
+            }
+        }
+    }
+
+    #[test]
+    fn positions_are_rounded_to_the_precision() {
+        let mut ghost = Ghost::new(0, 0);
+        ghost.frames.push(RagDoll::from_points(&[Vector2::new(1.05, -2.95); RagDoll::POINT_COUNT]));
+
+        let loaded = Ghost::from_bytes(&ghost.to_bytes()).unwrap();
+        for point in loaded.frames[0].get_points().iter() {
+            assert_eq!((point.x, point.y), (1.0625, -2.9375));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let bytes = ghost().to_bytes();
+
+        assert!(Ghost::from_bytes(&[]).is_none());
+        assert!(Ghost::from_bytes(b"RPG").is_none());
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(Ghost::from_bytes(&magic).is_none());
+
+        let mut version = bytes.clone();
+        version[4] = FORMAT_VERSION + 1;
+        assert!(Ghost::from_bytes(&version).is_none());
+
+        assert!(Ghost::from_bytes(&bytes[..HEADER_SIZE - 1]).is_none());
+        assert!(Ghost::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(Ghost::from_bytes(&extra).is_none());
+
+        let mut count = bytes.clone();
+        count[21] = 200;
+        assert!(Ghost::from_bytes(&count).is_none());
+    }
+}
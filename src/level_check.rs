@@ -0,0 +1,165 @@
+
+// Checks that a level can be completed by simulating the player.
+//
+// Usage: level_check <level.lvl> [max expansions]
+//
+// Exits with a non-zero status if no way to the exit was found.
+
+
+const TILE_SIZE: f64 = 48.0;
+
+
+#[macro_use]
+extern crate glium;
+extern crate trap;
+
+#[allow(dead_code)]
+mod rax;
+
+#[allow(dead_code)]
+mod player;
+#[allow(dead_code)]
+mod tile_map;
+#[allow(dead_code)]
+mod simulation;
+mod fnv;
+#[allow(dead_code)]
+mod movement_config;
+#[allow(dead_code)]
+mod verlet;
+#[allow(dead_code)]
+mod animation;
+
+mod solver;
+
+use std::process;
+
+use trap::Vector2i;
+
+use tile_map::TileMap;
+use solver::{Solver, Report, HeldInput};
+use simulation::Simulation;
+use movement_config::{MovementConfig, MOVEMENT_CONFIG_PATH};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.is_empty() {
+        println!("Usage: level_check <level.lvl> [max expansions]");
+        process::exit(2);
+    }
+
+    let path = &args[0];
+    let max_expansions = match args.get(1) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            println!("Invalid number of expansions: '{}'", arg);
+            process::exit(2);
+        }),
+        None => 20000,
+    };
+
+    let tile_map = match TileMap::open(path, TILE_SIZE) {
+        Ok(tile_map) => tile_map,
+        Err(e) => {
+            println!("{}: failed to load level: {}", path, e);
+            process::exit(2);
+        }
+    };
+
+    if tile_map.get_exit().is_none() {
+        println!("{}: level has no exit", path);
+        process::exit(2);
+    }
+
+    let movement = MovementConfig::open(MOVEMENT_CONFIG_PATH).unwrap_or_else(MovementConfig::new);
+
+    let report = Solver::new(&tile_map, movement, max_expansions).solve();
+
+    print_map(&tile_map, &report);
+
+    let tiles = tile_map.get_tiles();
+    println!("Expanded {} states{}", report.expanded, if report.complete { "" } else { " (search incomplete)" });
+    println!("Stood on {} of {} tiles", report.reached_tiles.len(), tiles.len());
+
+    if let Some(ref witness) = report.witness {
+        let total: u32 = witness.iter().map(|&(_, ticks)| ticks).sum();
+        println!("Exit reached after {} ticks ({:.2} s):", total, total as f64 / 240.0);
+
+        for &(input, ticks) in witness.iter() {
+            println!("    {:>5} ticks: {}", ticks, input);
+        }
+
+        // Playing the same inputs twice has to end up in exactly the same state
+        let first = replay(&tile_map, movement, witness);
+        let second = replay(&tile_map, movement, witness);
+
+        if first != second {
+            println!("{}: replaying the inputs is not deterministic ({:016x} != {:016x})", path, first, second);
+            process::exit(1);
+        }
+
+        println!("Final state hash: {:016x}", first);
+    } else {
+        println!("{}: exit is not reachable", path);
+        process::exit(1);
+    }
+}
+
+
+/// Plays a sequence of inputs from the start of a level and returns the hash of the final state
+fn replay(tile_map: &TileMap, movement: MovementConfig, inputs: &[(HeldInput, u32)]) -> u64 {
+    let mut simulation = Simulation::new(tile_map.clone());
+    simulation.set_movement_config(movement);
+    let mut previous = HeldInput::idle();
+
+    for &(input, ticks) in inputs {
+        for _ in 0..ticks {
+            simulation.step(&input.commands(previous));
+            previous = input;
+        }
+    }
+
+    simulation.state_hash()
+}
+
+
+/// Prints the map, marking every tile and cell the player reached
+///
+/// '#' tile, '=' tile stood on, '.' cell passed through, 'S' start, 'E' exit
+fn print_map(tile_map: &TileMap, report: &Report) {
+    let tiles = tile_map.get_tiles();
+
+    let mut cells: Vec<Vector2i> = tiles.iter().map(|&(pos, _)| pos).collect();
+    cells.extend(report.visited_cells.iter().cloned());
+    cells.push(tile_map.get_player_start());
+    cells.extend(tile_map.get_exit());
+
+    let left = cells.iter().map(|c| c.x).min().unwrap();
+    let right = cells.iter().map(|c| c.x).max().unwrap();
+    let top = cells.iter().map(|c| c.y).min().unwrap();
+    let bottom = cells.iter().map(|c| c.y).max().unwrap();
+
+    for y in top..bottom + 1 {
+        let mut row = String::new();
+
+        for x in left..right + 1 {
+            let cell = Vector2i::new(x, y);
+
+            row.push(if Some(cell) == tile_map.get_exit() {
+                'E'
+            } else if cell == tile_map.get_player_start() {
+                'S'
+            } else if report.reached_tiles.contains(&cell) {
+                '='
+            } else if tile_map.get_tile(cell).is_some() {
+                '#'
+            } else if report.visited_cells.contains(&cell) {
+                '.'
+            } else {
+                ' '
+            });
+        }
+
+        println!("{}", row.trim_end());
+    }
+}
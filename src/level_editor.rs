@@ -1,7 +1,13 @@
+use std::any::Any;
+
 use trap::{Vector2, Vector2i};
 
 use rax::{Game, Renderer};
 use rax::{MouseButton, KeyCode};
+use rax::view::View;
+use rax::scene::Scene;
+use rax::event::Event;
+use rax::input::Input;
 
 use rax::collision::*;
 
@@ -9,16 +15,36 @@ use tile_map::{TileMap, Tile};
 
 use ::TILE_SIZE;
 
+
+/// How far the map can be zoomed out and in
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 4.0;
+
+/// How much the zoom changes for every line the mouse wheel is scrolled
+const ZOOM_STEP: f64 = 1.1;
+
+/// Where levels are saved to, by name
+const LEVEL_DIRECTORY: &str = "levels";
+
+
 pub struct LevelEditor {
     tile_map: TileMap,
 
-    camera_center: Vector2,
+    // The view the map was last rendered with, to find what is under the mouse
+    view: View,
+    zoom: f64,
     window_size: Vector2i,
 
+    // The name being typed to save the level under, if any
+    level_name: Option<String>,
+
     current_tile: Vector2i,
 
     selection: Option<[Vector2i; 2]>,
 
+    // The selection places and removes dash pickups instead of tiles
+    editing_pickups: bool,
+
     tile_panel: TilePanel,
 
     map_area: Rectangle,
@@ -29,13 +55,19 @@ impl LevelEditor {
     pub fn new() -> Self {
         LevelEditor {
             tile_map: TileMap::new(TILE_SIZE),
-            camera_center: Vector2::new(0.0, 0.0),
+            view: View::new(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0)),
+            zoom: 1.0,
 
             window_size: Vector2i::new(0, 0),
+
+            level_name: None,
+
             current_tile: Vector2i::new(0, 0),
 
             selection: None,
 
+            editing_pickups: false,
+
             tile_panel: TilePanel::new(),
 
             map_area: Rectangle::new(0, 0, 0, 0),
@@ -44,16 +76,22 @@ impl LevelEditor {
     }
 
 
-    fn screen_to_world(&self, screen: Vector2i) -> Vector2 {
-        let width = self.map_area.right - self.map_area.left;
-        let height = self.map_area.bottom - self.map_area.top;
-
-        Vector2::new(
-            screen.x as f64 + self.camera_center.x - width as f64 / 2.0,
-            screen.y as f64 + self.camera_center.y - height as f64 / 2.0,
-        )
+    /// Saves the level under the name that was typed, an empty name cancels
+    fn save_level(&mut self) {
+        if let Some(name) = self.level_name.take() {
+            if name.is_empty() {
+                println!("Save cancelled");
+            } else {
+                let path = format!("{}/{}.lvl", LEVEL_DIRECTORY, name);
+                match self.tile_map.save(&path) {
+                    Ok(()) => println!("Saved level to '{}'", path),
+                    Err(e) => println!("Failed to save level to '{}': {}", path, e),
+                }
+            }
+        }
     }
 
+
     fn world_to_tile(&self, world: Vector2) -> Vector2i {
         let tile_size = self.tile_map.get_tile_size();
 
@@ -95,9 +133,34 @@ impl LevelEditor {
 }
 
 impl Game for LevelEditor {
-    fn update(&mut self, _dt: f64) {}
+    fn update(&mut self, _dt: f64, input: &Input) {
+        if self.map_area.contains(input.get_cursor_position()) {
+            self.current_tile = self.world_to_tile(input.get_cursor_world(&self.view));
+
+            if let Some(ref mut selection) = self.selection {
+                selection[1] = self.current_tile;
+            }
+        }
+
+        if self.level_name.is_some() {
+            if input.was_pressed_this_frame(KeyCode::Return) {
+                self.save_level();
+            } else if input.was_pressed_this_frame(KeyCode::Back) {
+                if let Some(ref mut name) = self.level_name {
+                    name.pop();
+                    println!("Level name: {}", name);
+                }
+            }
+        } else if input.get_modifiers().ctrl && input.was_pressed_this_frame(KeyCode::S) {
+            println!("Type a name for the level and press return to save it");
+            self.level_name = Some(String::new());
+        } else if input.was_pressed_this_frame(KeyCode::P) {
+            self.editing_pickups = !self.editing_pickups;
+            println!("Editing {}", if self.editing_pickups { "dash pickups" } else { "tiles" });
+        }
+    }
 
-    fn render(&mut self, renderer: &mut Renderer) {
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64) {
         renderer.set_viewport(
             self.map_area.left as u32,
             self.map_area.right as u32,
@@ -105,18 +168,30 @@ impl Game for LevelEditor {
             self.map_area.bottom as u32,
         );
 
-        self.camera_center = renderer.get_center();
+        renderer.set_zoom(self.zoom);
+        self.view = renderer.get_view();
 
         renderer.clear(0.05, 0.05, 0.2);
 
         self.tile_map.draw(renderer);
 
+        renderer.color = [0.3, 0.9, 1.0, 0.8];
+        for &pickup in self.tile_map.get_dash_pickups() {
+            self.tile_map.draw_dash_pickup(renderer, pickup);
+        }
+
         let (left, right, top, bottom) = self.tile_to_rect(self.current_tile);
         renderer.color = [0.0, 0.0, 0.0, 1.0];
         renderer.draw_rectangle(left, right, top, bottom);
 
 
         for tile in self.get_selected_tiles() {
+            if self.editing_pickups {
+                renderer.color = [0.0, 1.0, 0.0, 0.2];
+                self.tile_map.draw_dash_pickup(renderer, tile);
+                continue;
+            }
+
             let (left, right, top, bottom) = self.tile_to_rect(tile);
 
             let mut convex = self.tile_panel.get_current_tile().get_convex_hull(TILE_SIZE);
@@ -133,18 +208,38 @@ impl Game for LevelEditor {
             self.panel_area.top as u32,
             self.panel_area.bottom as u32,
         );
-        self.tile_panel.render(renderer);
+        self.tile_panel.render(renderer, alpha);
     }
 
     fn is_running(&self) -> bool {
         true
     }
 
+    fn on_event(&mut self, event: Event) {
+        match event {
+            Event::MouseWheel(_, lines) => {
+                self.zoom = (self.zoom * ZOOM_STEP.powf(lines)).max(MIN_ZOOM).min(MAX_ZOOM);
+            },
+
+            // Control characters, such as from backspace, are handled as keys
+            Event::Character(character) if !character.is_control() => {
+                if let Some(ref mut name) = self.level_name {
+                    if character.is_alphanumeric() || character == '_' || character == '-' {
+                        name.push(character);
+                        println!("Level name: {}", name);
+                    }
+                }
+            },
+
+            _ => ()
+        }
+    }
+
     fn on_mouse_press(&mut self, button: MouseButton, x: u64, y: u64) {
         let screen = Vector2i::new(x as i64, y as i64);
 
         if self.map_area.contains(screen) {
-            let tile = self.world_to_tile(self.screen_to_world(Vector2i::new(x as i64, y as i64)));
+            let tile = self.world_to_tile(self.view.screen_to_world(screen));
 
             self.selection = Some([tile, tile]);
         } else if self.panel_area.contains(screen) {
@@ -158,12 +253,20 @@ impl Game for LevelEditor {
         if self.map_area.contains(screen) {
             if button == MouseButton::Left {
                 for tile in self.get_selected_tiles() {
-                    self.tile_map.add_tile(tile, self.tile_panel.get_current_tile())
+                    if self.editing_pickups {
+                        self.tile_map.add_dash_pickup(tile)
+                    } else {
+                        self.tile_map.add_tile(tile, self.tile_panel.get_current_tile())
+                    }
                 }
                 self.selection = None;
             } else if button == MouseButton::Right {
                 for tile in self.get_selected_tiles() {
-                    self.tile_map.remove_tile(tile)
+                    if self.editing_pickups {
+                        self.tile_map.remove_dash_pickup(tile)
+                    } else {
+                        self.tile_map.remove_tile(tile)
+                    }
                 }
                 self.selection = None;
             }
@@ -175,13 +278,7 @@ impl Game for LevelEditor {
     fn on_mouse_move(&mut self, x: u64, y: u64) {
         let screen = Vector2i::new(x as i64, y as i64);
 
-        if self.map_area.contains(screen) {
-            self.current_tile = self.world_to_tile(self.screen_to_world([x as i64, y as i64].into()));
-
-            if let Some(ref mut selection) = self.selection {
-                selection[1] = self.current_tile;
-            }
-        } else if self.panel_area.contains(screen) {
+        if self.panel_area.contains(screen) {
             self.tile_panel.on_mouse_move(x - self.panel_area.left as u64, y - self.panel_area.top as u64);
         }
     }
@@ -208,6 +305,13 @@ impl Game for LevelEditor {
 }
 
 
+impl Scene for LevelEditor {
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+
 struct TilePanel {
     tiles: Vec<Tile>,
     bounding_boxes: Vec<(ConvexHull, Tile)>,
@@ -271,9 +375,9 @@ impl TilePanel {
 }
 
 impl Game for TilePanel {
-    fn update(&mut self, dt: f64) {}
+    fn update(&mut self, dt: f64, _input: &Input) {}
 
-    fn render(&mut self, renderer: &mut Renderer) {
+    fn render(&mut self, renderer: &mut Renderer, _alpha: f64) {
         renderer.set_center(Vector2::from(self.render_size) / 2.0);
 
         renderer.color = [0.05, 0.05, 0.05, 1.0];
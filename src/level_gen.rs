@@ -0,0 +1,91 @@
+
+// Generates a random level that the player can get through, and writes it to a level file.
+//
+// Usage: level_gen <output.lvl> [--seed N] [--length N] [--difficulty F] [--slopes F] [--wall-jumps N]
+
+
+const TILE_SIZE: f64 = 48.0;
+
+
+#[macro_use]
+extern crate glium;
+extern crate trap;
+
+#[allow(dead_code)]
+mod rax;
+
+#[allow(dead_code)]
+mod player;
+#[allow(dead_code)]
+mod tile_map;
+#[allow(dead_code)]
+mod movement_config;
+#[allow(dead_code)]
+mod verlet;
+#[allow(dead_code)]
+mod animation;
+#[allow(dead_code)]
+mod simulation;
+mod fnv;
+#[allow(dead_code)]
+mod solver;
+
+#[allow(dead_code)]
+mod level_generator;
+
+use std::process;
+use std::str::FromStr;
+
+use level_generator::LevelGenerator;
+use movement_config::{MovementConfig, MOVEMENT_CONFIG_PATH};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.is_empty() {
+        println!("Usage: level_gen <output.lvl> [--seed N] [--length N] [--difficulty F] [--slopes F] [--wall-jumps N]");
+        process::exit(2);
+    }
+
+    let path = &args[0];
+    let movement = MovementConfig::open(MOVEMENT_CONFIG_PATH).unwrap_or_else(MovementConfig::new);
+    let mut generator = LevelGenerator::new(0).with_movement_config(movement);
+
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let value = options.next().unwrap_or_else(|| {
+            println!("Missing value for '{}'", option);
+            process::exit(2);
+        });
+
+        generator = match option.as_str() {
+            "--seed" => generator.with_seed(parse(option, value)),
+            "--length" => generator.with_length(parse(option, value)),
+            "--difficulty" => generator.with_difficulty(parse(option, value)),
+            "--slopes" => generator.with_slope_density(parse(option, value)),
+            "--wall-jumps" => generator.with_wall_jump_sections(parse(option, value)),
+
+            _ => {
+                println!("Unknown option '{}'", option);
+                process::exit(2);
+            }
+        };
+    }
+
+    let tile_map = generator.generate(TILE_SIZE);
+
+    if let Err(e) = tile_map.save(path) {
+        println!("Failed to save level: {}", e);
+        process::exit(1);
+    }
+
+    println!("Generated level with seed {} to {}", generator.get_seed(), path);
+}
+
+
+fn parse<T: FromStr>(option: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        println!("Invalid value for '{}': '{}'", option, value);
+        process::exit(2);
+    })
+}
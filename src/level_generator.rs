@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+
+use trap::Vector2i;
+
+use tile_map::{TileMap, Tile};
+use movement_config::MovementConfig;
+use solver::Solver;
+
+
+/// Number of times a piece of a level is replaced when the player can not get across it, before
+/// flat ground is placed instead
+const MAX_ATTEMPTS: u32 = 8;
+
+/// Number of states the solver may expand while looking for a way across a piece of a level
+const MAX_SOLVER_EXPANSIONS: usize = 16000;
+
+
+/// Generates random levels from a seed
+///
+/// The level is built from left to right out of random terrain and hand-authored chunks,
+/// all of which can be traversed by running, jumping and wall-jumping.
+#[derive(Clone)]
+pub struct LevelGenerator {
+    seed: u64,
+
+    // Number of columns in the level
+    length: u64,
+
+    // In the range [0, 1]
+    difficulty: f64,
+    slope_density: f64,
+
+    wall_jump_sections: u64,
+
+    // How the player moves when the level is checked
+    movement: MovementConfig,
+}
+
+
+/// A hand-authored piece of a level
+///
+/// The bottom row is placed at the current ground height, and the top of the tiles stacked
+/// on the bottom of the last column becomes the new ground height.
+/// '#' is a square, '/' a wedge sloping up to the right and '\' a wedge sloping down.
+struct Chunk {
+    rows: &'static [&'static str],
+
+    difficulty: f64,
+    wall_jump: bool,
+}
+
+
+const CHUNKS: &[Chunk] = &[
+    // Stairs
+    Chunk {
+        rows: &[
+            "....##",
+            "..####",
+            "######",
+        ],
+        difficulty: 0.0,
+        wall_jump: false,
+    },
+
+    // Hill
+    Chunk {
+        rows: &[
+            "..../##\\..",
+            ".../####\\.",
+            "###########",
+        ],
+        difficulty: 0.0,
+        wall_jump: false,
+    },
+
+    // Tunnel
+    Chunk {
+        rows: &[
+            "###########",
+            "...........",
+            "...........",
+            "###########",
+        ],
+        difficulty: 0.2,
+        wall_jump: false,
+    },
+
+    // Pit with a floating platform
+    Chunk {
+        rows: &[
+            "...###...",
+            ".........",
+            "##.....##",
+        ],
+        difficulty: 0.4,
+        wall_jump: false,
+    },
+
+    // Stepping stones
+    Chunk {
+        rows: &[
+            ".......#.....",
+            "....#........",
+            ".#.........##",
+            "#..........##",
+        ],
+        difficulty: 0.7,
+        wall_jump: false,
+    },
+
+    // Shaft climbed by jumping between the walls
+    Chunk {
+        rows: &[
+            "#....###",
+            "#....###",
+            "#..#####",
+            "#..#####",
+            "#..#####",
+            "...#####",
+            "...#####",
+            "########",
+        ],
+        difficulty: 0.5,
+        wall_jump: true,
+    },
+
+    // Taller and wider shaft
+    Chunk {
+        rows: &[
+            "#.....###",
+            "#.....###",
+            "#...#####",
+            "#...#####",
+            "#...#####",
+            "#...#####",
+            "....#####",
+            "....#####",
+            "#########",
+        ],
+        difficulty: 0.8,
+        wall_jump: true,
+    },
+];
+
+
+/// The level being generated
+#[derive(Clone)]
+struct Layout {
+    tiles: HashMap<Vector2i, Tile>,
+
+    // Columns that are filled from their surface down to the bottom of the level
+    ground: Vec<(i64, i64)>,
+
+    // Next column to place tiles in
+    x: i64,
+
+    // Row of the topmost tile of the ground
+    surface: i64,
+}
+
+
+/// A small, seeded pseudo-random number generator (xorshift64*)
+struct Random {
+    state: u64,
+}
+
+
+impl LevelGenerator {
+    pub fn new(seed: u64) -> LevelGenerator {
+        LevelGenerator {
+            seed,
+            length: 100,
+            difficulty: 0.3,
+            slope_density: 0.3,
+            wall_jump_sections: 1,
+            movement: MovementConfig::new(),
+        }
+    }
+
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        LevelGenerator { seed, .. self }
+    }
+
+
+    pub fn with_length(self, length: u64) -> Self {
+        LevelGenerator { length, .. self }
+    }
+
+
+    pub fn with_difficulty(self, difficulty: f64) -> Self {
+        LevelGenerator { difficulty: clamp(difficulty), .. self }
+    }
+
+
+    pub fn with_slope_density(self, slope_density: f64) -> Self {
+        LevelGenerator { slope_density: clamp(slope_density), .. self }
+    }
+
+
+    pub fn with_wall_jump_sections(self, wall_jump_sections: u64) -> Self {
+        LevelGenerator { wall_jump_sections, .. self }
+    }
+
+
+    pub fn with_movement_config(self, movement: MovementConfig) -> Self {
+        LevelGenerator { movement, .. self }
+    }
+
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+
+    pub fn get_difficulty(&self) -> f64 {
+        self.difficulty
+    }
+
+
+    /// Generates a new level
+    ///
+    /// Every piece of the level is checked with the solver as it is placed, and replaced if the
+    /// player can not get across it from the end of the piece before.
+    pub fn generate(&self, tile_size: f64) -> TileMap {
+        let mut random = Random::new(self.seed);
+
+        let mut layout = Layout {
+            tiles: HashMap::new(),
+            ground: Vec::new(),
+            x: 0,
+            surface: 0,
+        };
+
+        // Give the player some room to start running
+        layout.flat(4);
+
+        // Spread the wall jump sections evenly over the level
+        let mut wall_jumps: Vec<i64> = (0..self.wall_jump_sections)
+            .map(|i| ((i + 1) * self.length / (self.wall_jump_sections + 1)) as i64)
+            .collect();
+        wall_jumps.reverse();
+
+        let end = self.length as i64 - 4;
+        while layout.x < end {
+            let wall_jump = wall_jumps.last().map_or(false, |&x| layout.x >= x);
+            if wall_jump {
+                wall_jumps.pop();
+            }
+
+            // Chunks the player could not get across here, as indices into CHUNKS. Whether a chunk
+            // can be crossed depends on how the player comes into it, so they are only ruled out
+            // until the next piece is placed.
+            let mut rejected = Vec::new();
+
+            let mut placed = false;
+            for _ in 0..MAX_ATTEMPTS {
+                let mut piece = layout.clone();
+
+                let chunk = if wall_jump {
+                    self.wall_jump_section(&mut piece, &mut random, &rejected)
+                } else {
+                    self.segment(&mut piece, &mut random, &rejected)
+                };
+
+                // A shaft without any chunk left to use is skipped
+                if wall_jump && chunk.is_none() {
+                    break;
+                }
+
+                if self.can_cross(&layout, &piece, tile_size) {
+                    layout = piece;
+                    placed = true;
+                    break;
+                }
+
+                if let Some(chunk) = chunk {
+                    rejected.push(chunk);
+                }
+            }
+
+            // Flat ground can always be crossed
+            if !placed {
+                layout.flat(2);
+            }
+        }
+
+        // The exit sits on a short run of flat ground
+        layout.flat(4);
+        let exit = Vector2i::new(layout.x - 2, layout.surface - 1);
+
+        // Walls on both sides keep the player inside the level
+        let top = layout.tiles.keys().map(|p| p.y).min().unwrap_or(0) - 4;
+        for y in top..layout.surface {
+            layout.set(-1, y, Tile::Square);
+            layout.set(layout.x, y, Tile::Square);
+        }
+        layout.ground.push((-1, top));
+        let right = layout.x;
+        layout.ground.push((right, top));
+
+        let mut tile_map = TileMap::new(tile_size);
+
+        for (pos, tile) in layout.finish() {
+            tile_map.add_tile(pos, tile);
+        }
+
+        tile_map.set_player_start(Vector2i::new(1, -1));
+        tile_map.set_exit(Some(exit));
+
+        tile_map
+    }
+
+
+    /// Returns true if the solver finds a way over the tiles placed since an earlier layout,
+    /// starting on the last column before them
+    fn can_cross(&self, before: &Layout, after: &Layout, tile_size: f64) -> bool {
+        let start = Vector2i::new(before.x - 1, before.surface - 1);
+        let exit = Vector2i::new(after.x - 1, after.surface - 1);
+
+        let mut tile_map = after.window(start.x - 2, after.x, tile_size);
+        tile_map.set_player_start(start);
+        tile_map.set_exit(Some(exit));
+
+        Solver::new(&tile_map, self.movement, MAX_SOLVER_EXPANSIONS)
+            .with_stop_at_exit(true)
+            .solve()
+            .witness
+            .is_some()
+    }
+
+
+    /// Adds a shaft that is climbed by jumping between its walls, and returns the chunk used. Levels
+    /// easier than every shaft get the easiest one.
+    fn wall_jump_section(&self, layout: &mut Layout, random: &mut Random, rejected: &[usize]) -> Option<usize> {
+        let usable = |i: &usize| CHUNKS[*i].wall_jump && !rejected.contains(i);
+
+        let chunks: Vec<usize> = (0..CHUNKS.len())
+            .filter(|i| usable(i) && CHUNKS[*i].difficulty <= self.difficulty + 0.3)
+            .collect();
+
+        let chunk = random.choose(&chunks).cloned().or_else(|| {
+            (0..CHUNKS.len())
+                .filter(usable)
+                .min_by(|a, b| CHUNKS[*a].difficulty.partial_cmp(&CHUNKS[*b].difficulty).unwrap())
+        });
+
+        if let Some(chunk) = chunk {
+            layout.chunk(&CHUNKS[chunk]);
+            layout.flat(2);
+        }
+
+        chunk
+    }
+
+
+    /// Adds a random piece of terrain to the level, and returns the chunk used if there was one
+    fn segment(&self, layout: &mut Layout, random: &mut Random, rejected: &[usize]) -> Option<usize> {
+        let difficulty = self.difficulty;
+
+        if random.chance(0.15 + 0.2 * difficulty) {
+            let chunks: Vec<usize> = (0..CHUNKS.len())
+                .filter(|i| !CHUNKS[*i].wall_jump && CHUNKS[*i].difficulty <= difficulty && !rejected.contains(i))
+                .collect();
+
+            if let Some(&chunk) = random.choose(&chunks) {
+                layout.chunk(&CHUNKS[chunk]);
+                layout.flat(1);
+                return Some(chunk);
+            }
+        }
+
+        match random.range(0, 3) {
+            // Flat ground
+            0 => {
+                let width = random.range(2, 5);
+                layout.flat(width);
+            }
+
+            // Step up
+            1 => {
+                let height = random.range(1, 1 + (difficulty * 1.5).round() as i64);
+                if random.chance(self.slope_density) {
+                    for _ in 0..height {
+                        layout.ramp_up(random.chance(0.5));
+                    }
+                } else {
+                    layout.surface -= height;
+                }
+                layout.flat(random.range(2, 3));
+            }
+
+            // Step down
+            2 => {
+                let height = random.range(1, 2 + (difficulty * 2.0).round() as i64);
+                if random.chance(self.slope_density) {
+                    for _ in 0..height {
+                        layout.ramp_down(random.chance(0.5));
+                    }
+                } else {
+                    layout.surface += height;
+                }
+                layout.flat(random.range(2, 3));
+            }
+
+            // Gap
+            _ => {
+                let width = random.range(1, 1 + (difficulty * 3.0).round() as i64);
+                layout.x += width;
+
+                // Landing slightly higher makes the jump harder
+                if random.chance(difficulty) {
+                    layout.surface -= 1;
+                }
+                layout.flat(random.range(2, 4));
+            }
+        }
+
+        None
+    }
+}
+
+
+impl Layout {
+    /// Places a tile
+    fn set(&mut self, x: i64, y: i64, tile: Tile) {
+        self.tiles.insert(Vector2i::new(x, y), tile);
+    }
+
+
+    /// Adds flat ground at the current height
+    fn flat(&mut self, width: i64) {
+        for _ in 0..width {
+            let (x, surface) = (self.x, self.surface);
+            self.set(x, surface, Tile::Square);
+            self.ground.push((x, surface));
+            self.x += 1;
+        }
+    }
+
+
+    /// Raises the ground one tile, either steeply or over two columns
+    fn ramp_up(&mut self, steep: bool) {
+        let (x, surface) = (self.x, self.surface);
+
+        if steep {
+            self.set(x, surface - 1, Tile::WedgeUpLeft);
+            self.set(x, surface, Tile::Square);
+            self.ground.push((x, surface));
+            self.x += 1;
+        } else {
+            self.set(x, surface - 1, Tile::SlantedWedgeUpLeft);
+            self.set(x + 1, surface - 1, Tile::SlantUpLeft);
+            self.set(x, surface, Tile::Square);
+            self.set(x + 1, surface, Tile::Square);
+            self.ground.push((x, surface));
+            self.ground.push((x + 1, surface));
+            self.x += 2;
+        }
+
+        // The flat side of the slope has to be backed by ground
+        self.surface -= 1;
+        self.flat(1);
+    }
+
+
+    /// Lowers the ground one tile, either steeply or over two columns
+    fn ramp_down(&mut self, steep: bool) {
+        let (x, surface) = (self.x, self.surface);
+
+        if steep {
+            self.set(x, surface, Tile::WedgeUpRight);
+            self.ground.push((x, surface));
+            self.x += 1;
+        } else {
+            self.set(x, surface, Tile::SlantUpRight);
+            self.set(x + 1, surface, Tile::SlantedWedgeUpRight);
+            self.ground.push((x, surface));
+            self.ground.push((x + 1, surface));
+            self.x += 2;
+        }
+
+        self.surface += 1;
+    }
+
+
+    /// Stamps a chunk at the current position
+    fn chunk(&mut self, chunk: &Chunk) {
+        let height = chunk.rows.len() as i64;
+        let width = chunk.rows.iter().map(|r| r.len()).max().unwrap_or(0) as i64;
+
+        for (row, line) in chunk.rows.iter().enumerate() {
+            let y = self.surface - (height - 1 - row as i64);
+
+            for (column, c) in line.chars().enumerate() {
+                let x = self.x + column as i64;
+
+                let tile = match c {
+                    '#' => Tile::Square,
+                    '/' => Tile::WedgeUpLeft,
+                    '\\' => Tile::WedgeUpRight,
+                    _ => continue,
+                };
+
+                self.set(x, y, tile);
+
+                if row as i64 == height - 1 {
+                    self.ground.push((x, y));
+                }
+            }
+        }
+
+        // Climb the last column from the bottom to find the new surface
+        let last = self.x + width - 1;
+        let mut y = self.surface;
+        if self.tiles.contains_key(&Vector2i::new(last, y)) {
+            while self.tiles.contains_key(&Vector2i::new(last, y - 1)) {
+                y -= 1;
+            }
+            self.surface = y;
+        }
+
+        self.x += width;
+    }
+
+
+    /// Returns the tiles in a range of columns as a tile map, with the ground below them filled in
+    fn window(&self, left: i64, right: i64, tile_size: f64) -> TileMap {
+        let inside = |x: i64| x >= left && x < right;
+
+        let layout = Layout {
+            tiles: self.tiles.iter()
+                .filter(|&(pos, _)| inside(pos.x))
+                .map(|(&pos, &tile)| (pos, tile))
+                .collect(),
+            ground: self.ground.iter().cloned().filter(|&(x, _)| inside(x)).collect(),
+            x: self.x,
+            surface: self.surface,
+        };
+
+        let mut tile_map = TileMap::new(tile_size);
+
+        for (pos, tile) in layout.finish() {
+            tile_map.add_tile(pos, tile);
+        }
+
+        tile_map
+    }
+
+
+    /// Fills the ground down to a common bottom and returns all tiles
+    fn finish(mut self) -> HashMap<Vector2i, Tile> {
+        let bottom = self.ground.iter().map(|&(_, y)| y).max().unwrap_or(0) + 2;
+
+        for (x, surface) in self.ground.clone() {
+            for y in surface + 1..bottom + 1 {
+                let pos = Vector2i::new(x, y);
+                if !self.tiles.contains_key(&pos) {
+                    self.tiles.insert(pos, Tile::Square);
+                }
+            }
+        }
+
+        self.tiles
+    }
+}
+
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Scramble the seed (splitmix64) so that similar seeds give different sequences
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        // The state must never be zero
+        Random { state: if z == 0 { 1 } else { z } }
+    }
+
+
+    /// Returns a random number in the range [0, 2^64)
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+
+    /// Returns a random number in the range [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+
+    /// Returns a random number in the range [min, max]
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+
+        min + (self.next() % (max - min + 1) as u64) as i64
+    }
+
+
+    /// Returns true with a probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+
+    /// Returns a random item from a slice, or None if it is empty
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.range(0, items.len() as i64 - 1) as usize])
+        }
+    }
+}
+
+
+fn clamp(value: f64) -> f64 {
+    if value < 0.0 { 0.0 } else if value > 1.0 { 1.0 } else { value }
+}
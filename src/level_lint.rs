@@ -0,0 +1,258 @@
+
+// Checks level files for common mistakes.
+//
+// Usage: level_lint <level.lvl>...
+//
+// Exits with a non-zero status if any level contains errors.
+
+
+const TILE_SIZE: f64 = 48.0;
+
+
+#[macro_use]
+extern crate glium;
+extern crate trap;
+
+#[allow(dead_code)]
+mod rax;
+
+#[allow(dead_code)]
+mod player;
+#[allow(dead_code)]
+mod tile_map;
+#[allow(dead_code)]
+mod movement_config;
+#[allow(dead_code)]
+mod verlet;
+#[allow(dead_code)]
+mod animation;
+#[allow(dead_code)]
+mod fnv;
+
+use std::fmt;
+use std::fs::File;
+use std::process;
+
+use trap::Vector2i;
+
+use tile_map::{TileMap, Tile, Direction};
+
+fn main() {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        eprintln!("Usage: level_lint <level.lvl>...");
+        process::exit(2);
+    }
+
+    let mut errors = 0;
+    for path in paths.iter() {
+        let problems = lint(path);
+
+        for problem in problems.iter() {
+            eprintln!("{}: {}", path, problem);
+        }
+
+        errors += problems.iter().filter(|p| p.severity == Severity::Error).count();
+    }
+
+    if errors > 0 {
+        eprintln!("{} error(s) found", errors);
+        process::exit(1);
+    }
+}
+
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+
+/// A problem found in a level
+struct Problem {
+    severity: Severity,
+    message: String,
+}
+
+
+impl Problem {
+    fn error(message: String) -> Problem {
+        Problem { severity: Severity::Error, message }
+    }
+
+    fn warning(message: String) -> Problem {
+        Problem { severity: Severity::Warning, message }
+    }
+}
+
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+
+/// Returns all problems found in a level file
+fn lint(path: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let text = {
+        use std::io::Read;
+
+        let mut text = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut text)) {
+            Ok(_) => text,
+            Err(e) => {
+                problems.push(Problem::error(format!("failed to read file: {}", e)));
+                return problems;
+            }
+        }
+    };
+
+    // The tile map skips tiles it does not recognize, so look for them in the source
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some("TILE") {
+            if let Some(id) = words.next() {
+                let name = id.trim_matches('\"');
+                if Tile::from_name(name).is_none() {
+                    problems.push(Problem::error(format!("line {}: unknown tile '{}'", number + 1, name)));
+                }
+            }
+        }
+    }
+
+    let tile_map = match TileMap::from_str(&text, TILE_SIZE) {
+        Ok(tile_map) => tile_map,
+        Err(e) => {
+            problems.push(Problem::error(e));
+            return problems;
+        }
+    };
+
+    let tiles = tile_map.get_tiles();
+    if tiles.is_empty() {
+        problems.push(Problem::error("level contains no tiles".to_owned()));
+        return problems;
+    }
+
+    check_player_start(&tile_map, &tiles, &mut problems);
+    check_exit(&tile_map, &tiles, &mut problems);
+
+    for pickup in tile_map.get_dash_pickups() {
+        check_object("dash pickup", *pickup, &tile_map, &tiles, &mut problems);
+    }
+    check_slopes(&tile_map, &tiles, &mut problems);
+
+    problems
+}
+
+
+/// Checks that the player starts on solid ground, inside the map
+fn check_player_start(tile_map: &TileMap, tiles: &[(Vector2i, Tile)], problems: &mut Vec<Problem>) {
+    let start = tile_map.get_player_start();
+
+    check_object("player start", start, tile_map, tiles, problems);
+
+    let grounded = tiles.iter().any(|&(pos, _)| pos.x == start.x && pos.y > start.y);
+    if !grounded {
+        problems.push(Problem::error(format!(
+            "player start {}:{} has no ground beneath it", start.x, start.y
+        )));
+    }
+}
+
+
+/// Checks that the level has an exit inside the map
+fn check_exit(tile_map: &TileMap, tiles: &[(Vector2i, Tile)], problems: &mut Vec<Problem>) {
+    if let Some(exit) = tile_map.get_exit() {
+        check_object("exit", exit, tile_map, tiles, problems);
+    } else {
+        problems.push(Problem::warning("level has no exit".to_owned()));
+    }
+}
+
+
+/// Checks that an object is inside the map and not inside a tile
+fn check_object(name: &str, pos: Vector2i, tile_map: &TileMap, tiles: &[(Vector2i, Tile)], problems: &mut Vec<Problem>) {
+    // Levels are open towards the sky, so objects above the highest tile are fine
+    let (min, max) = tile_bounds(tiles);
+    if pos.x < min.x || pos.x > max.x || pos.y > max.y {
+        problems.push(Problem::error(format!(
+            "{} {}:{} is outside the map bounds ({}:{} to {}:{})",
+            name, pos.x, pos.y, min.x, min.y, max.x, max.y
+        )));
+    }
+
+    if let Some(tile) = tile_map.get_tile(pos) {
+        problems.push(Problem::error(format!(
+            "{} {}:{} is inside a {} tile", name, pos.x, pos.y, tile
+        )));
+    }
+}
+
+
+/// Checks that the flat edges of slopes are covered by neighbouring tiles
+fn check_slopes(tile_map: &TileMap, tiles: &[(Vector2i, Tile)], problems: &mut Vec<Problem>) {
+    for &(pos, tile) in tiles.iter() {
+        if tile == Tile::Square {
+            continue;
+        }
+
+        let mut exposed = Vec::new();
+        for direction in Direction::all().iter() {
+            // Only the flat edges of a slope are internal
+            if !tile.is_solid(direction.opposite()) {
+                continue;
+            }
+
+            let covered = match tile_map.get_tile(pos + direction.as_delta()) {
+                Some(neighbour) => neighbour.is_solid(*direction),
+                None => false,
+            };
+
+            if !covered {
+                exposed.push(direction_name(*direction));
+            }
+        }
+
+        if !exposed.is_empty() {
+            problems.push(Problem::warning(format!(
+                "{} tile at {}:{} has exposed internal edges: {}",
+                tile, pos.x, pos.y, exposed.join(", ")
+            )));
+        }
+    }
+}
+
+
+/// Returns the smallest and largest tile coordinates in a map
+fn tile_bounds(tiles: &[(Vector2i, Tile)]) -> (Vector2i, Vector2i) {
+    let mut min = tiles[0].0;
+    let mut max = tiles[0].0;
+
+    for &(pos, _) in tiles.iter() {
+        if pos.x < min.x { min.x = pos.x; }
+        if pos.x > max.x { max.x = pos.x; }
+        if pos.y < min.y { min.y = pos.y; }
+        if pos.y > max.y { max.y = pos.y; }
+    }
+
+    (min, max)
+}
+
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "top",
+        Direction::Down => "bottom",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
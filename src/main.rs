@@ -3,7 +3,6 @@
 
 // TODO: Add better level editing support
 
-// TODO: Add exit
 // TODO: Add "traps"        - bombs, static lasers, etc.
 // TODO: Add coins
 // TODO: Add level timer
@@ -23,18 +22,39 @@ extern crate trap;
 mod rax;
 use rax::{Game, Renderer};
 use rax::{KeyCode, MouseButton};
+use rax::actions::ActionMap;
+use rax::scene::{Scene, SceneStack, Transition};
+use rax::event::Event;
+use rax::input::Input;
 
 mod frame_counter;
+mod controls;
+mod camera;
 
 mod player;
 mod tile_map;
+mod level_generator;
+#[allow(dead_code)]
+mod solver;
+mod simulation;
+mod fnv;
+mod replay;
+mod ghost;
+mod movement_config;
+mod verlet;
+mod animation;
 
 mod runplusplus;
 use runplusplus::RunPlusPlus;
 
+mod race;
+use race::Race;
+
 mod level_editor;
 use level_editor::LevelEditor;
 
+mod pause;
+
 fn main() {
     println!("Hello, world!");
 
@@ -43,186 +63,169 @@ fn main() {
         .with_size(1280, 720)
         .with_fullscreen(false)
         .with_vsync(true)
-        .with_samples(8);
+        .with_samples(8)
+        .with_fixed_timestep(simulation::TICK_RATE as f64);
 
     game.run(MainGame::new());
 }
 
 
-/// Directs events to the correct mode
+/// How long the screen takes to fade out, and back in, when switching modes, in seconds
+const MODE_FADE_TIME: f64 = 0.2;
+
+
+/// Switches between modes, and directs events to the current one
 struct MainGame {
-    current_mode: GameMode,
+    scenes: SceneStack,
+
+    // Bindings for switching between modes
+    actions: ActionMap,
 
     running: bool,
-    window_size: [u64; 2]
 }
 
 
-/// Possible game modes
+/// Possible game modes, told apart by the scene being played
+#[derive(Copy, Clone, Eq, PartialEq)]
 enum GameMode {
-    Game(Box<RunPlusPlus>),
-    LevelEditor(Box<LevelEditor>)
+    Game,
+    Race(usize),
+    LevelEditor,
 }
 
 impl MainGame {
     pub fn new() -> Self {
+        // A replay file can be given on the command line to watch it
+        let game = match std::env::args().nth(1) {
+            Some(path) => RunPlusPlus::from_replay(&path).unwrap_or_else(RunPlusPlus::new),
+            None => RunPlusPlus::new(),
+        };
+
+        let mut scenes = SceneStack::new();
+        scenes.push(Box::new(game));
+
         MainGame {
-            current_mode: GameMode::Game(Box::new(RunPlusPlus::new())),
+            scenes,
+
+            actions: controls::load_bindings(),
 
             running: true,
-            window_size: [0, 0],
         }
     }
-}
 
 
-impl Game for MainGame {
-    fn update(&mut self, dt: f64) {
-        self.current_mode.update(dt)
-    }
+    /// Returns the mode of the scene below any overlays
+    fn current_mode(&self) -> Option<GameMode> {
+        let scene = self.scenes.get_base()?.as_any();
 
-    fn render(&mut self, renderer: &mut Renderer) {
-        self.current_mode.render(renderer)
+        if scene.is::<RunPlusPlus>() {
+            Some(GameMode::Game)
+        } else if let Some(race) = scene.downcast_ref::<Race>() {
+            Some(GameMode::Race(race.get_player_count()))
+        } else if scene.is::<LevelEditor>() {
+            Some(GameMode::LevelEditor)
+        } else {
+            None
+        }
     }
 
-    fn is_running(&self) -> bool {
-        self.running && self.current_mode.is_running()
-    }
 
-    fn on_close(&mut self) {
-        self.running = false;
-        self.current_mode.on_close();
+    /// Fades over to another mode, closing any overlays on top of the current one
+    fn switch_mode(&mut self, scene: Box<Scene>) {
+        while self.scenes.len() > 1 {
+            self.scenes.pop();
+        }
+
+        self.scenes.transition(Transition::Fade(MODE_FADE_TIME, Box::new(Transition::Replace(scene))));
     }
 
-    fn on_key_press(&mut self, key: KeyCode) {
-        self.current_mode.on_key_press(key);
 
-        if key == KeyCode::Escape {
+    /// Switches modes or quits when one of the mode actions is started
+    fn on_action_start(&mut self, action: &str) {
+        if action == "QUIT" {
             self.running = false;
         }
-        if key == KeyCode::F1 {
-            if !self.current_mode.is_game() {
-                self.current_mode.on_close();
-                self.current_mode = GameMode::Game(Box::new(RunPlusPlus::new()));
-                self.current_mode.on_size_change(self.window_size[0], self.window_size[1]);
-            }
-        }
-        if key == KeyCode::F2 {
-            if !self.current_mode.is_level_editor() {
-                self.current_mode.on_close();
-                self.current_mode = GameMode::LevelEditor(Box::new(LevelEditor::new()));
-                self.current_mode.on_size_change(self.window_size[0], self.window_size[1]);
+        if action == "PLAY" {
+            if self.current_mode() != Some(GameMode::Game) {
+                self.switch_mode(Box::new(RunPlusPlus::new()));
             }
         }
-    }
-
-    fn on_key_release(&mut self, key: KeyCode) {
-        self.current_mode.on_key_release(key)
-    }
-
-    fn on_mouse_press(&mut self, button: MouseButton, x: u64, y: u64) {
-        self.current_mode.on_mouse_press(button, x, y)
-    }
+        if action == "ENDLESS_RUN" {
+            use std::time::{SystemTime, UNIX_EPOCH};
+            let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
 
-    fn on_mouse_release(&mut self, button: MouseButton, x: u64, y: u64) {
-        self.current_mode.on_mouse_release(button, x, y)
-    }
-
-    fn on_mouse_move(&mut self, x: u64, y: u64) {
-        self.current_mode.on_mouse_move(x, y)
-    }
+            self.switch_mode(Box::new(RunPlusPlus::endless(seed)));
+        }
+        if action == "RACE" {
+            // Every press starts the race over with another player, up to the most there can be
+            let players = match self.current_mode() {
+                Some(GameMode::Race(players)) if players < race::MAX_PLAYERS => players + 1,
+                _ => race::MIN_PLAYERS,
+            };
 
-    fn on_size_change(&mut self, width: u64, height: u64) {
-        self.window_size = [width, height];
-        self.current_mode.on_size_change(width, height)
+            let race = Race::open(runplusplus::DEFAULT_LEVEL_PATH, players);
+            self.switch_mode(Box::new(race));
+        }
+        if action == "EDIT_LEVEL" {
+            if self.current_mode() != Some(GameMode::LevelEditor) {
+                self.switch_mode(Box::new(LevelEditor::new()));
+            }
+        }
     }
 }
 
 
-impl GameMode {
-    pub fn is_game(&self) -> bool {
-        match *self {
-            GameMode::Game(_) => true,
-            _ => false
-        }
-    }
+impl Game for MainGame {
+    fn update(&mut self, dt: f64, input: &Input) {
+        self.scenes.update(dt, input);
 
-    pub fn is_level_editor(&self) -> bool {
-        match *self {
-            GameMode::LevelEditor(_) => true,
-            _ => false
+        for action in self.actions.get_started(input) {
+            self.on_action_start(&action);
         }
     }
-}
-
 
-impl Game for GameMode {
-    fn update(&mut self, dt: f64) {
-        match *self {
-            GameMode::Game(ref mut game) => game.update(dt),
-            GameMode::LevelEditor(ref mut editor) => editor.update(dt),
-        }
+    fn fixed_update(&mut self, dt: f64, input: &Input) {
+        self.scenes.fixed_update(dt, input)
     }
 
-    fn render(&mut self, renderer: &mut Renderer) {
-        match *self {
-            GameMode::Game(ref mut game) => game.render(renderer),
-            GameMode::LevelEditor(ref mut editor) => editor.render(renderer),
-        }
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64) {
+        self.scenes.render(renderer, alpha)
     }
 
     fn is_running(&self) -> bool {
-        match *self {
-            GameMode::Game(ref game) => game.is_running(),
-            GameMode::LevelEditor(ref editor) => editor.is_running(),
-        }
+        self.running && self.scenes.is_running()
     }
+
+    fn on_event(&mut self, event: Event) {
+        self.scenes.on_event(event)
+    }
+
     fn on_close(&mut self) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_close(),
-            GameMode::LevelEditor(ref mut editor) => editor.on_close(),
-        }
+        self.running = false;
+        self.scenes.on_close();
     }
 
     fn on_key_press(&mut self, key: KeyCode) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_key_press(key),
-            GameMode::LevelEditor(ref mut editor) => editor.on_key_press(key),
-        }
+        self.scenes.on_key_press(key)
     }
 
     fn on_key_release(&mut self, key: KeyCode) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_key_release(key),
-            GameMode::LevelEditor(ref mut editor) => editor.on_key_release(key),
-        }
+        self.scenes.on_key_release(key)
     }
 
     fn on_mouse_press(&mut self, button: MouseButton, x: u64, y: u64) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_mouse_press(button, x, y),
-            GameMode::LevelEditor(ref mut editor) => editor.on_mouse_press(button, x, y),
-        }
+        self.scenes.on_mouse_press(button, x, y)
     }
 
     fn on_mouse_release(&mut self, button: MouseButton, x: u64, y: u64) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_mouse_release(button, x, y),
-            GameMode::LevelEditor(ref mut editor) => editor.on_mouse_release(button, x, y),
-        }
+        self.scenes.on_mouse_release(button, x, y)
     }
 
     fn on_mouse_move(&mut self, x: u64, y: u64) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_mouse_move(x, y),
-            GameMode::LevelEditor(ref mut editor) => editor.on_mouse_move(x, y),
-        }
+        self.scenes.on_mouse_move(x, y)
     }
 
     fn on_size_change(&mut self, width: u64, height: u64) {
-        match *self {
-            GameMode::Game(ref mut game) => game.on_size_change(width, height),
-            GameMode::LevelEditor(ref mut editor) => editor.on_size_change(width, height),
-        }
+        self.scenes.on_size_change(width, height)
     }
 }
@@ -0,0 +1,367 @@
+use std;
+use std::f64::INFINITY;
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use player;
+
+
+/// Where the movement parameters are loaded from
+pub const MOVEMENT_CONFIG_PATH: &str = "config/movement.cfg";
+
+
+/// Declares the movement parameters from a single list, so that the struct, the defaults, the
+/// names in the file and the hash can never get out of step. Every parameter has a range its
+/// value has to lie within.
+macro_rules! movement_parameters {
+    ($($(#[$doc:meta])* $field:ident: $kind:ty = $default:expr, $name:ident, $min:expr, $max:expr;)*) => {
+        /// Parameters that control how the player moves
+        ///
+        /// Speeds are in pixels per second, accelerations in pixels per second squared and factors are
+        /// the fraction of the velocity lost per second
+        #[derive(Copy, Clone, PartialEq, Debug)]
+        pub struct MovementConfig {
+            $($(#[$doc])* pub $field: $kind,)*
+        }
+
+
+        impl MovementConfig {
+            /// The parameters the game was designed with
+            pub fn new() -> MovementConfig {
+                MovementConfig {
+                    $($field: $default,)*
+                }
+            }
+
+
+            /// Sets the parameter with a name to a value written in a file. Returns None if the
+            /// value is invalid, and false if there is no parameter with that name.
+            fn set(&mut self, name: &str, value: &str) -> Option<bool> {
+                $(
+                    if name == stringify!($name) {
+                        self.$field = parse_value(name, value, $min, $max)?;
+                        return Some(true);
+                    }
+                )*
+
+                Some(false)
+            }
+        }
+
+
+        impl Hash for MovementConfig {
+            // Only the bits of every value are written, as a float and in a fixed order, so that
+            // hashes saved to disk stay the same
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                $(self.$field.to_f64().to_bits().hash(state);)*
+            }
+        }
+
+
+        impl fmt::Display for MovementConfig {
+            // Written in the same format as the config file
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                $(writeln!(f, "{} {}", stringify!($name), self.$field)?;)*
+                Ok(())
+            }
+        }
+    }
+}
+
+
+/// The most ticks any timing parameter can last, ten seconds
+const MAX_TICKS: u32 = 2400;
+
+
+movement_parameters! {
+    /// Speed gained when jumping off the ground
+    jump_impulse: f64 = 250.0, JUMP_IMPULSE, 0.0, INFINITY;
+
+    /// Speed gained when jumping off a wall, and how much more it points up than away from the wall
+    wall_jump_impulse: f64 = 250.0, WALL_JUMP_IMPULSE, 0.0, INFINITY;
+    wall_jump_lift: f64 = 1.5, WALL_JUMP_LIFT, 0.0, INFINITY;
+
+    /// Gravity while moving up and while falling
+    rise_gravity: f64 = 200.0, RISE_GRAVITY, 0.0, INFINITY;
+    fall_gravity: f64 = 400.0, FALL_GRAVITY, 0.0, INFINITY;
+
+    /// Acceleration while running on the ground
+    run_acceleration: f64 = 300.0, RUN_ACCELERATION, 0.0, INFINITY;
+
+    /// Fraction of the run acceleration available in the air
+    air_control: f64 = 0.75, AIR_CONTROL, 0.0, 1.0;
+
+    horizontal_drag: f64 = 1.0, HORIZONTAL_DRAG, 0.0, INFINITY;
+    vertical_drag: f64 = 0.5, VERTICAL_DRAG, 0.0, INFINITY;
+
+    /// Friction while sliding down a wall, normally and while holding slide
+    wall_friction: f64 = 9.0, WALL_FRICTION, 0.0, INFINITY;
+    wall_slide_friction: f64 = 2.0, WALL_SLIDE_FRICTION, 0.0, INFINITY;
+
+    /// Number of ticks after leaving the ground, or a wall, during which a jump is still allowed
+    coyote_ticks: u32 = 24, COYOTE_TICKS, 0, MAX_TICKS;
+    wall_coyote_ticks: u32 = 24, WALL_COYOTE_TICKS, 0, MAX_TICKS;
+
+    /// Number of ticks a jump pressed in the air is remembered, to happen once the player lands
+    jump_buffer_ticks: u32 = 24, JUMP_BUFFER_TICKS, 0, MAX_TICKS;
+
+    /// Fraction of the upward speed kept when jump is released while rising
+    jump_cut_factor: f64 = 0.4, JUMP_CUT_FACTOR, 0.0, 1.0;
+
+    /// Height every jump reaches, even if jump is released right away
+    min_jump_height: f64 = 24.0, MIN_JUMP_HEIGHT, 0.0, INFINITY;
+
+    /// How far in front of the player, and how far below the top of the player, a ledge can be grabbed
+    ledge_reach: f64 = 6.0, LEDGE_REACH, 0.0, INFINITY;
+    ledge_grab_depth: f64 = 16.0, LEDGE_GRAB_DEPTH, 0.0, INFINITY;
+
+    /// How far the top of the player sticks out above the ledge while hanging
+    ledge_hang_height: f64 = 4.0, LEDGE_HANG_HEIGHT, 0.0, INFINITY;
+
+    /// Number of ticks it takes to climb onto a ledge
+    mantle_ticks: u32 = 48, MANTLE_TICKS, 0, MAX_TICKS;
+
+    /// Number of ticks after letting go of a ledge before another can be grabbed
+    ledge_regrab_ticks: u32 = 48, LEDGE_REGRAB_TICKS, 0, MAX_TICKS;
+
+    /// Speed and duration, in ticks, of a dash
+    dash_speed: f64 = 600.0, DASH_SPEED, 0.0, INFINITY;
+    dash_ticks: u32 = 36, DASH_TICKS, 0, MAX_TICKS;
+
+    /// Fraction of the dash speed kept when a dash ends
+    dash_end_speed: f64 = 0.4, DASH_END_SPEED, 0.0, 1.0;
+
+    /// Number of dashes available between landings
+    dash_charges: u32 = 1, DASH_CHARGES, 0, 100;
+
+    /// Height of the player's hull while crouching, at most as tall as while standing
+    crouch_height: f64 = 22.0, CROUCH_HEIGHT, 1.0, player::HEIGHT;
+
+    /// Fraction of the run acceleration available while crouching
+    crouch_control: f64 = 0.4, CROUCH_CONTROL, 0.0, 1.0;
+
+    /// Crouching faster than this is a slide, which can not be sped up
+    slide_min_speed: f64 = 150.0, SLIDE_MIN_SPEED, 0.0, INFINITY;
+}
+
+
+impl MovementConfig {
+    /// Open a config file, any parameter missing from the file keeps its default value
+    pub fn open<P: AsRef<Path>>(path: P) -> Option<MovementConfig> {
+        if let Ok(mut file) = File::open(path) {
+            use std::io::Read;
+
+            let mut string = String::new();
+            if file.read_to_string(&mut string).is_err() {
+                return None;
+            }
+
+            MovementConfig::from_str(&string)
+        } else {
+            None
+        }
+    }
+
+
+    /// Parses a string of parameter names and values, lines starting with '#' are comments
+    fn from_str(text: &str) -> Option<MovementConfig> {
+        let mut config = MovementConfig::new();
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+
+            let name = match words.next() {
+                Some(name) if !name.starts_with('#') => name,
+                _ => continue,
+            };
+
+            let value = if let Some(value) = words.next() {
+                value
+            } else {
+                println!("{}: expected a number!", name);
+                return None;
+            };
+
+            if !config.set(name, value)? {
+                println!("Skipping unknown movement parameter '{}'", name);
+            }
+        }
+
+        Some(config)
+    }
+}
+
+
+/// A type of movement parameter
+trait Parameter: Copy + PartialOrd + fmt::Display + FromStr {
+    /// Returns false for infinities and NaN
+    fn is_finite(self) -> bool;
+
+    fn to_f64(self) -> f64;
+}
+
+impl Parameter for f64 {
+    fn is_finite(self) -> bool {
+        f64::is_finite(self)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Parameter for u32 {
+    fn is_finite(self) -> bool {
+        true
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+
+/// Parses the value of a parameter, which has to be a finite number from min to max
+fn parse_value<T: Parameter>(name: &str, text: &str, min: T, max: T) -> Option<T> {
+    let value = if let Ok(value) = text.parse::<T>() {
+        value
+    } else {
+        println!("{}: '{}' is not a valid number!", name, text);
+        return None;
+    };
+
+    if !value.is_finite() {
+        println!("{}: expected a finite number!", name);
+        None
+    } else if value < min {
+        println!("{}: {} is less than {}!", name, value, min);
+        None
+    } else if value > max {
+        println!("{}: {} is more than {}!", name, value, max);
+        None
+    } else {
+        Some(value)
+    }
+}
+
+
+/// Keeps a movement config up to date with the file it was loaded from
+pub struct MovementConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+
+    config: MovementConfig,
+}
+
+
+impl MovementConfigWatcher {
+    /// Load a config file, using the defaults until the file can be loaded
+    pub fn new<P: AsRef<Path>>(path: P) -> MovementConfigWatcher {
+        let mut watcher = MovementConfigWatcher {
+            path: path.as_ref().to_path_buf(),
+            modified: None,
+            config: MovementConfig::new(),
+        };
+
+        if !watcher.poll() {
+            println!("Failed to load {}, using default movement", watcher.path.display());
+        }
+
+        watcher
+    }
+
+
+    /// Reloads the config if the file changed since it was last loaded, returns true if it was reloaded
+    ///
+    /// A file that fails to load leaves the current config untouched, so that half written edits
+    /// do not break the game
+    pub fn poll(&mut self) -> bool {
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        if let Some(config) = MovementConfig::open(&self.path) {
+            self.config = config;
+            true
+        } else {
+            false
+        }
+    }
+
+
+    pub fn get_config(&self) -> MovementConfig {
+        self.config
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut config = MovementConfig::new();
+        config.jump_impulse = 312.125;
+        config.air_control = 0.1;
+        config.coyote_ticks = 7;
+        config.dash_charges = 3;
+
+        let loaded = MovementConfig::from_str(&config.to_string()).unwrap();
+        assert_eq!(loaded, config);
+    }
+
+    #[test]
+    fn defaults_match_the_config_file() {
+        assert_eq!(MovementConfig::open(MOVEMENT_CONFIG_PATH), Some(MovementConfig::new()));
+    }
+
+    #[test]
+    fn skips_comments_and_unknown_parameters() {
+        let config = MovementConfig::from_str("# A comment\n\nJUMP_IMPULSE 100\nFLY_SPEED 20\nDASH_TICKS 12").unwrap();
+
+        let mut expected = MovementConfig::new();
+        expected.jump_impulse = 100.0;
+        expected.dash_ticks = 12;
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(MovementConfig::from_str("JUMP_IMPULSE").is_none());
+        assert!(MovementConfig::from_str("JUMP_IMPULSE high").is_none());
+        assert!(MovementConfig::from_str("JUMP_IMPULSE NaN").is_none());
+        assert!(MovementConfig::from_str("JUMP_IMPULSE inf").is_none());
+        assert!(MovementConfig::from_str("JUMP_IMPULSE -1").is_none());
+        assert!(MovementConfig::from_str("AIR_CONTROL 1.5").is_none());
+        assert!(MovementConfig::from_str("CROUCH_HEIGHT 0").is_none());
+        assert!(MovementConfig::from_str("COYOTE_TICKS 2.5").is_none());
+        assert!(MovementConfig::from_str("COYOTE_TICKS -1").is_none());
+        assert!(MovementConfig::from_str("COYOTE_TICKS 2401").is_none());
+        assert!(MovementConfig::from_str("DASH_CHARGES 101").is_none());
+    }
+
+    #[test]
+    fn hash_only_depends_on_values() {
+        use fnv::Fnv1a;
+
+        let hash = |config: &MovementConfig| {
+            let mut hasher = Fnv1a::new();
+            config.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut changed = MovementConfig::new();
+        changed.dash_ticks += 1;
+
+        assert_eq!(hash(&MovementConfig::new()), hash(&MovementConfig::new()));
+        assert!(hash(&MovementConfig::new()) != hash(&changed));
+    }
+}
@@ -0,0 +1,93 @@
+use std::any::Any;
+
+use trap::Vector2;
+
+use rax::{Game, Renderer};
+use rax::actions::ActionMap;
+use rax::scene::{Scene, Transition};
+use rax::input::Input;
+
+
+/// Drawn over a level while it is paused. The level below is still drawn, but not updated, until
+/// the overlay is closed.
+pub struct PauseOverlay {
+    // Bindings for the action that resumes the game
+    actions: ActionMap,
+
+    resumed: bool,
+
+    window_size: Vector2,
+}
+
+
+impl PauseOverlay {
+    pub fn new(actions: ActionMap) -> PauseOverlay {
+        PauseOverlay {
+            actions,
+
+            resumed: false,
+
+            window_size: Vector2::new(0.0, 0.0),
+        }
+    }
+}
+
+
+impl Game for PauseOverlay {
+    fn update(&mut self, _dt: f64, input: &Input) {
+        if self.actions.was_started("PAUSE", input) {
+            self.resumed = true;
+        }
+    }
+
+    fn render(&mut self, renderer: &mut Renderer, _alpha: f64) {
+        let (width, height) = (self.window_size.x, self.window_size.y);
+
+        renderer.set_viewport(0, width as u32, 0, height as u32);
+        renderer.set_center(self.window_size / 2.0);
+        renderer.set_zoom(1.0);
+        renderer.set_rotation(0.0);
+
+        renderer.color = [0.0, 0.0, 0.0, 0.5];
+        renderer.fill_rectangle(0.0, width, 0.0, height);
+
+        // Two bars, like the pause button of a music player
+        let center = self.window_size / 2.0;
+        let (bar_width, bar_height, gap) = (16.0, 64.0, 16.0);
+
+        renderer.color = [1.0, 1.0, 1.0, 0.8];
+        for &side in [-1.0, 1.0].iter() {
+            let left = center.x + side * (gap + bar_width) / 2.0 - bar_width / 2.0;
+            renderer.fill_rectangle(left, left + bar_width, center.y - bar_height / 2.0, center.y + bar_height / 2.0);
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        true
+    }
+
+
+    fn on_size_change(&mut self, width: u64, height: u64) {
+        self.window_size = Vector2::new(width as f64, height as f64);
+    }
+}
+
+
+impl Scene for PauseOverlay {
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn take_transition(&mut self) -> Option<Transition> {
+        if self.resumed {
+            self.resumed = false;
+            Some(Transition::Pop)
+        } else {
+            None
+        }
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
@@ -1,15 +1,27 @@
 
+use std::hash::Hasher;
+
 use ::TILE_SIZE;
 
-use trap::Vector2;
+use movement_config::MovementConfig;
+use verlet::VerletRagDoll;
+use animation::{AnimationSet, Pose};
+use tile_map::{TileMap, Direction};
+use fnv::Fnv1a;
+
+use trap::{Vector2, Vector2i};
 use rax::collision::*;
 
 use rax::Renderer;
 
+#[derive(Clone)]
 pub struct Player {
     collision: ConvexHull,
     rag_doll: RagDoll,
 
+    // Moves the rag doll around once the player is dead
+    corpse: Option<VerletRagDoll>,
+
     center: Vector2,
     velocity: Vector2,
 
@@ -19,14 +31,91 @@ pub struct Player {
     ground_normal: Option<Vector2>,
     wall_normal: Option<Vector2>,
 
-    jumping: bool,
+    // The last ground and wall touched, and the number of ticks since they were touched
+    last_ground: Option<(Vector2, u32)>,
+    last_wall: Option<(Vector2, u32)>,
+
+    // Number of ticks since jump was pressed, until the jump happens or is forgotten
+    jump_buffer: Option<u32>,
+    jump_held: bool,
+
+    // Height the player jumped from, while still rising and the jump can be cut short
+    jump_start: Option<f64>,
+
+    // The ledge the player is hanging from
+    ledge: Option<Ledge>,
+
+    // Ticks left until a ledge can be grabbed again after letting go
+    ledge_cooldown: u32,
+
+    // Vertical direction held this tick, -1 for up and 1 for down
+    aim: f64,
+
+    dash: Option<Dash>,
+    dash_requested: bool,
+
+    drop_requested: bool,
+
+    // Dashes left until the player lands or touches a pickup
+    dash_charges: u32,
+
+    // The player's hull is lowered while crouching
+    crouching: bool,
+    crouch_held: bool,
+
     sliding: bool,
 
+    state: PlayerState,
+
+    // Events emitted during the last update
+    events: Vec<PlayerEvent>,
+
     commands: Vec<PlayerCommand>,
 }
 
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+/// What the player is doing. The state decides how the player moves during an update, and
+/// what it moves to at the end of it.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum PlayerState {
+    Grounded,
+    Airborne,
+    WallSliding,
+
+    // Rising from a jump, while the jump can still be cut short
+    Jumping,
+    WallJumping,
+
+    Crouching,
+    Sliding,
+
+    Hanging,
+    Climbing,
+
+    Dashing,
+
+    Dead,
+}
+
+
+/// Something that happened to the player during an update, for effects to react to
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum PlayerEvent {
+    Jumped,
+    WallJumped,
+    Landed,
+
+    WallGrabbed,
+    LedgeGrabbed,
+    Mantled,
+
+    Dashed,
+
+    Died,
+}
+
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
 pub enum PlayerCommand {
     MoveLeft,
     MoveRight,
@@ -35,19 +124,62 @@ pub enum PlayerCommand {
     StopJump,
 
     Drop,
-    Slide
+    Slide,
+    Crouch,
+
+    AimUp,
+    AimDown,
+
+    Dash,
 }
 
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
 enum MoveDirection {
     Left,
     Right,
 }
 
 
+/// A ledge the player is holding on to
+#[derive(Clone)]
+struct Ledge {
+    direction: MoveDirection,
+
+    // The corner of the tile that is held
+    grip: Vector2,
+
+    // Where the player's center is while hanging and after climbing up
+    hang_center: Vector2,
+    stand_center: Vector2,
+
+    // Number of ticks spent climbing, once the player started climbing up
+    climb: Option<u32>,
+}
+
+
+/// A dash in progress
+#[derive(Clone)]
+struct Dash {
+    direction: Vector2,
+
+    // Number of ticks since the dash started
+    ticks: u32,
+}
+
+
+/// Most surfaces the player can hit and slide along during a single update
+const MAX_SWEEPS: u32 = 4;
+
+/// Size of the player's hull while standing
+pub const WIDTH: f64 = TILE_SIZE / 2.0;
+pub const HEIGHT: f64 = TILE_SIZE - 3.0;
+
+
+
 /// Stores the positions of all limb endpoints
-struct RagDoll {
+#[derive(Clone)]
+pub struct RagDoll {
     shoulder: Vector2,
     arm_joints: [Vector2; 2],
     hands: [Vector2; 2],
@@ -59,9 +191,9 @@ struct RagDoll {
 
 
 impl Player {
-    pub fn new(position: Vector2) -> Player {
-        let w = TILE_SIZE / 2.0;
-        let h = TILE_SIZE - 3.0;
+    pub fn new(position: Vector2, animations: &AnimationSet) -> Player {
+        let w = WIDTH;
+        let h = HEIGHT;
         let mut player = Player {
             //collision: Circle::new(position, 45.0 / 2.0),
             collision: ConvexHull::from_points(&[
@@ -79,6 +211,8 @@ impl Player {
                 feet: [Vector2::new(0.0, 0.0); 2],
             },
 
+            corpse: None,
+
             center: position,
             velocity: Vector2 { x: 0.0, y: 0.0 },
 
@@ -88,24 +222,76 @@ impl Player {
             ground_normal: None,
             wall_normal: None,
 
-            jumping: false,
+            last_ground: None,
+            last_wall: None,
+
+            jump_buffer: None,
+            jump_held: false,
+
+            jump_start: None,
+
+            ledge: None,
+            ledge_cooldown: 0,
+
+            aim: 0.0,
+
+            dash: None,
+            dash_requested: false,
+            dash_charges: 0,
+
+            drop_requested: false,
+
+            crouching: false,
+            crouch_held: false,
+
             sliding: false,
 
+            state: PlayerState::Airborne,
+            events: Vec::new(),
+
             commands: Vec::new(),
         };
 
-        player.rag_doll = player.get_rag_doll();
+        player.rag_doll = player.get_rag_doll(animations);
 
         player
     }
 
 
     /// Update the player's position and movement
-    pub fn update(&mut self, dt: f64, obstacles: &[&Collide<ConvexHull>]) {
-        self.handle_commands(dt);
+    pub fn update(&mut self, dt: f64, movement: &MovementConfig, animations: &AnimationSet, tile_map: &TileMap, obstacles: &[&Collide<ConvexHull>]) {
+        self.events.clear();
+
+        if self.state == PlayerState::Dead {
+            self.commands.clear();
+            self.update_corpse(dt, movement, obstacles);
+            return;
+        }
+
+        self.handle_commands(dt, movement);
+
+        let state = match self.state {
+            PlayerState::Hanging | PlayerState::Climbing => self.update_ledge(dt, movement, tile_map, obstacles),
+            PlayerState::Dashing => self.update_dash(dt, movement, obstacles),
+            _ => self.update_movement(dt, movement, tile_map, obstacles),
+        };
+        self.set_state(state);
+
+        self.update_rag_doll(dt, animations, obstacles);
+    }
 
-        self.velocity.x -= self.velocity.x * dt * 1.0;
-        self.velocity.y -= self.velocity.y * dt * 0.5;
+
+    /// Runs, jumps and falls, returns the state the player ends up in
+    fn update_movement(&mut self, dt: f64, movement: &MovementConfig, tile_map: &TileMap, obstacles: &[&Collide<ConvexHull>]) -> PlayerState {
+        self.update_crouch(movement, obstacles);
+
+        if self.dash_requested && self.dash_charges > 0 {
+            self.start_dash();
+            return self.update_dash(dt, movement, obstacles);
+        }
+
+        self.velocity.x -= self.velocity.x * dt * movement.horizontal_drag;
+        self.velocity.y -= self.velocity.y * dt * movement.vertical_drag;
 
         if let Some(normal) = self.wall_normal {
             let dot = normal.dot(Vector2::new(-1.0, 0.0));
@@ -113,45 +299,174 @@ impl Player {
                 dot > 0.95 {
                 if self.velocity.y > 0.0 {
                     if self.sliding {
-                        self.velocity.y -= self.velocity.y * dt * 2.0;
+                        self.velocity.y -= self.velocity.y * dt * movement.wall_slide_friction;
                     } else {
-                        self.velocity.y -= self.velocity.y * dt * 9.0;
+                        self.velocity.y -= self.velocity.y * dt * movement.wall_friction;
                     }
                 }
             }
         }
 
-        if self.jumping {
-            if let Some(normal) = self.ground_normal {
-                self.velocity += normal * 250.0;
-                self.jumping = false;
-                self.wall_normal = None;
-            } else if let Some(normal) = self.wall_normal {
-                self.velocity += (normal + Vector2::new(0.0, -1.5)).norm() * 250.0;
-                self.jumping = false;
-                self.wall_normal = None;
+        // A jump pressed shortly before landing, or shortly after leaving the ground or a wall, still happens
+        let mut jump = None;
+        if self.jump_buffer.is_some() {
+            if let Some((normal, ticks)) = self.last_ground {
+                if ticks > 0 && self.velocity.y > 0.0 {
+                    self.velocity.y = 0.0;
+                }
+
+                self.velocity += normal * movement.jump_impulse;
+                self.consume_jump();
+                self.jump_start = Some(self.center.y);
+                jump = Some(PlayerState::Jumping);
+            } else if let Some((normal, ticks)) = self.last_wall {
+                if ticks > 0 && self.velocity.y > 0.0 {
+                    self.velocity.y = 0.0;
+                }
+
+                self.velocity += (normal + Vector2::new(0.0, -movement.wall_jump_lift)).norm() * movement.wall_jump_impulse;
+                self.consume_jump();
+                self.jump_start = Some(self.center.y);
+                jump = Some(PlayerState::WallJumping);
             }
         }
 
+        self.cut_jump(movement);
+
         if self.velocity.y > 0.0 {
-            self.velocity.y += 400.0 * dt;
+            self.velocity.y += movement.fall_gravity * dt;
         } else {
-            self.velocity.y += 200.0 * dt;
+            self.velocity.y += movement.rise_gravity * dt;
         }
 
         let amount = self.velocity * dt;
-        self.translate(amount);
+        self.move_and_collide(amount, obstacles);
+
+        if self.ground_normal.is_some() {
+            self.dash_charges = movement.dash_charges;
+        }
+
+        self.update_jump_windows(movement);
+
+        if self.ledge_cooldown > 0 {
+            self.ledge_cooldown -= 1;
+        } else if self.check_ledge_grab(movement, tile_map, obstacles) {
+            return PlayerState::Hanging;
+        }
+
+        jump.unwrap_or_else(|| self.free_state(movement))
+    }
 
-        self.check_collisions(obstacles);
 
-        let rag_doll = self.get_rag_doll();
+    /// Lets the rag doll fall and tumble. The corpse starts from the animated pose, so it is only
+    /// drawn: the player's center stays where they died, and the simulated state does not depend
+    /// on the animations.
+    fn update_corpse(&mut self, dt: f64, movement: &MovementConfig, obstacles: &[&Collide<ConvexHull>]) {
+        if self.corpse.is_none() {
+            self.corpse = Some(VerletRagDoll::new(&self.rag_doll, self.velocity, dt));
+        }
+
+        if let Some(ref mut corpse) = self.corpse {
+            corpse.step(dt, movement.fall_gravity, obstacles);
+            self.rag_doll = corpse.get_pose();
+        }
+    }
+
+
+    /// Decides what the player is doing when moving freely, from what they are touching
+    fn free_state(&self, movement: &MovementConfig) -> PlayerState {
+        if self.is_on_ground() {
+            if !self.crouching {
+                PlayerState::Grounded
+            } else if self.velocity.len() > movement.slide_min_speed {
+                PlayerState::Sliding
+            } else {
+                PlayerState::Crouching
+            }
+        } else if self.wall_normal.is_some() {
+            PlayerState::WallSliding
+        } else if self.jump_start.is_some() && (self.state == PlayerState::Jumping || self.state == PlayerState::WallJumping) {
+            self.state
+        } else {
+            PlayerState::Airborne
+        }
+    }
+
+
+    /// Returns true if the ground was touched during this or the previous tick. Running into a wall
+    /// while standing on the ground touches the ground and the wall on alternating ticks.
+    fn is_on_ground(&self) -> bool {
+        match self.last_ground {
+            Some((_, ticks)) => ticks <= 1,
+            None => false,
+        }
+    }
+
+
+    /// Moves to a new state. Every event is emitted here, from the transition between states.
+    fn set_state(&mut self, state: PlayerState) {
+        let previous = self.state;
+        if state == previous {
+            return;
+        }
+
+        self.state = state;
+
+        let event = match (previous, state) {
+            (_, PlayerState::Jumping) => PlayerEvent::Jumped,
+            (_, PlayerState::WallJumping) => PlayerEvent::WallJumped,
+            (_, PlayerState::WallSliding) => PlayerEvent::WallGrabbed,
+            (_, PlayerState::Hanging) => PlayerEvent::LedgeGrabbed,
+            (_, PlayerState::Dashing) => PlayerEvent::Dashed,
+            (_, PlayerState::Dead) => PlayerEvent::Died,
+            (PlayerState::Climbing, _) => PlayerEvent::Mantled,
+            (previous, state) if !previous.is_on_ground() && state.is_on_ground() => PlayerEvent::Landed,
+            _ => return,
+        };
+
+        self.events.push(event);
+    }
+
+
+    /// Stops the player from moving for good, until the level is restarted
+    pub fn kill(&mut self) {
+        if self.state != PlayerState::Dead {
+            self.dash = None;
+            self.ledge = None;
+
+            self.set_state(PlayerState::Dead);
+        }
+    }
+
+
+    pub fn get_state(&self) -> PlayerState {
+        self.state
+    }
+
+
+    /// Returns the events emitted during the last update
+    pub fn get_events(&self) -> &[PlayerEvent] {
+        &self.events
+    }
+
+
+    pub fn is_dead(&self) -> bool {
+        self.state == PlayerState::Dead
+    }
+
+
+    /// Moves the rag doll towards the pose for the current state, then plants its limbs on the
+    /// ground and walls around it
+    fn update_rag_doll(&mut self, dt: f64, animations: &AnimationSet, obstacles: &[&Collide<ConvexHull>]) {
+        let rag_doll = self.get_rag_doll(animations);
+
         let factor = 15.0;
 
 
         self.rag_doll.hip += factor * dt * (rag_doll.hip - self.rag_doll.hip);
 
         // Keep the hip in the center
-        let delta = self.center - self.rag_doll.hip;
+        let delta = rag_doll.hip - self.rag_doll.hip;
 
         self.rag_doll.hip += delta;
         self.rag_doll.shoulder += factor * dt * (rag_doll.shoulder - self.rag_doll.shoulder) + delta;
@@ -162,188 +477,155 @@ impl Player {
             self.rag_doll.leg_joints[i] += factor * dt * (rag_doll.leg_joints[i] - self.rag_doll.leg_joints[i]) + delta;
             self.rag_doll.feet[i] += factor * dt * (rag_doll.feet[i] - self.rag_doll.feet[i]) + delta;
         }
+
+        // Planted after following the pose, so that the feet do not lag behind the ground moving
+        // under them and sink into it
+        let mut planted = self.rag_doll.clone();
+        self.plant_limbs(&mut planted, obstacles);
+        self.rag_doll = planted;
     }
 
 
-    /// Draw the player
-    pub fn draw(&self, renderer: &mut Renderer) {
+    /// Draw the player, a fraction of the way from a previous pose to the current one
+    pub fn draw(&self, renderer: &mut Renderer, previous: &RagDoll, alpha: f64) {
         {
             renderer.color = [0.0, 0.0, 1.0, 0.3];
             // renderer.fill_circle(self.collision.center, self.collision.radius);
             // renderer.fill_convex(self.collision.get_points());
 
-            renderer.color = [1.0, 1.0, 1.0, 1.0];
-            renderer.line_width = 2.0;
+            previous.lerp(&self.rag_doll, alpha).draw(renderer, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
 
-            let rag_doll = &self.rag_doll;
 
-            renderer.draw_rounded_line(rag_doll.shoulder, rag_doll.hip);
-            for i in 0..2 {
-                renderer.draw_rounded_line(rag_doll.shoulder, rag_doll.arm_joints[i]);
-                renderer.draw_rounded_line(rag_doll.arm_joints[i], rag_doll.hands[i]);
+    /// Returns the pose the player is drawn in
+    pub fn get_pose(&self) -> &RagDoll {
+        &self.rag_doll
+    }
 
-                renderer.draw_rounded_line(rag_doll.hip, rag_doll.leg_joints[i]);
-                renderer.draw_rounded_line(rag_doll.leg_joints[i], rag_doll.feet[i]);
-            }
+
+    /// Returns the pose for the current state
+    fn get_rag_doll(&self, animations: &AnimationSet) -> RagDoll {
+        let bounds = self.collision.bounding_box();
+        let mid = Vector2::new((bounds.left + bounds.right) / 2.0, (bounds.top + bounds.bottom) / 2.0);
+
+        // The hands hold on to the ledge, so hanging is not animated
+        if let Some(ref ledge) = self.ledge {
+            return self.get_hanging_rag_doll(ledge, mid, bounds.bottom - bounds.top);
         }
-    }
 
+        let facing_right = self.velocity.x > 0.0;
+
+        let (pose, facing_right) = match (self.state, self.wall_normal) {
+            (PlayerState::WallSliding, Some(normal)) => (animations.wall.sample(0.0), normal.x > 0.0),
+
+            (PlayerState::Crouching, _) => (animations.crouch.sample(0.0), self.face_direction == MoveDirection::Right),
+            (PlayerState::Sliding, _) => (animations.slide.sample(0.0), self.face_direction == MoveDirection::Right),
 
-    /// Returns the current rag doll
-    fn get_rag_doll(&self) -> RagDoll {
-        let mut rag_doll = RagDoll {
-            shoulder: Vector2::new(0.0, 0.0),
-            arm_joints: [Vector2::new(0.0, 0.0); 2],
-            hands: [Vector2::new(0.0, 0.0); 2],
-            hip: Vector2::new(0.0, 0.0),
-            leg_joints: [Vector2::new(0.0, 0.0); 2],
-            feet: [Vector2::new(0.0, 0.0); 2],
+            // The run cycle follows the distance travelled, so that the feet do not slip
+            _ if self.ground_normal.is_some() || self.state == PlayerState::Grounded => (animations.run.sample(mid.x), facing_right),
+
+            _ => (animations.fall.sample(self.velocity.y), facing_right),
         };
 
+        // Limbs keep their standing length while the hull is lowered
+        RagDoll::from_pose(mid, &pose, HEIGHT, facing_right)
+    }
+
+
+    /// Bends the limbs of a pose so that the feet stand on the ground under them, instead of on a
+    /// flat floor at the bottom of the hull, and the hands touch the wall while sliding down it
+    fn plant_limbs(&self, rag_doll: &mut RagDoll, obstacles: &[&Collide<ConvexHull>]) {
         let bounds = self.collision.bounding_box();
-        let left = bounds.left;
-        let right = bounds.right;
-        let top = bounds.top;
-        let bottom = bounds.bottom;
-
-        let mid = Vector2::new((left + right) / 2.0, (top + bottom) / 2.0);
-        let size = Vector2::new(right - left, bottom - top);
-
-        // Construct unit vector with an angle between x axis
-        let angle = |angle: f64| {
-            use std::f64::consts::PI;
-            let rad = angle / 180.0 * PI;
-            Vector2::new(rad.cos(), -rad.sin())
-        };
 
-        // Map value in range [0, 1] to range [min, max]
-        let map = |v: f64, min: f64, max: f64| { v * (max - min) + min };
+        let thigh = HEIGHT / 4.0;
+        let shin = HEIGHT / 4.0;
 
-        let fmod = |a: f64, b: f64| { a - (a / b).floor() * b };
+        if self.ledge.is_none() && self.is_on_ground() {
+            let mut targets = [None; 2];
 
-        if let Some(normal) = self.wall_normal {
-            let angle = |a: f64| {
-                angle(if normal.x > 0.0 { a } else { 180.0 - a })
-            };
+            for i in 0..2 {
+                let foot = rag_doll.feet[i];
 
-            rag_doll.hip.x = mid.x;
-            rag_doll.hip.y = mid.y;
-
-            rag_doll.shoulder = rag_doll.hip + size.y / 3.0 * angle(75.0);
-
-            rag_doll.arm_joints = [
-                rag_doll.shoulder + size.y / 5.0 * angle(160.0),
-                rag_doll.shoulder + size.y / 5.0 * angle(210.0)
-            ];
-            rag_doll.hands = [
-                rag_doll.arm_joints[0] + size.y / 5.0 * angle(140.0),
-                rag_doll.arm_joints[1] + size.y / 5.0 * angle(140.0)
-            ];
-
-            rag_doll.leg_joints = [
-                rag_doll.hip + size.y / 4.0 * angle(130.0),
-                rag_doll.hip + size.y / 4.0 * angle(240.0),
-            ];
-
-            rag_doll.feet = [
-                rag_doll.leg_joints[0] + size.y / 4.0 * angle(240.0),
-                rag_doll.leg_joints[1] + size.y / 4.0 * angle(240.0),
-            ];
-
-            // Climbing wall
-        } else if let Some(_) = self.ground_normal {
-            let angle = |a: f64| {
-                angle(if self.velocity.x > 0.0 { a } else { 180.0 - a })
-            };
+                // The foot stays as high above the ground as it is above the bottom of the hull
+                let lift = (bounds.bottom - foot.y).max(0.0);
 
-            let mut p = fmod(mid.x, size.x * 4.0) / (size.x * 4.0) * 2.0;
+                let origin = Vector2::new(foot.x, rag_doll.hip.y);
+                let direction = Vector2::new(0.0, thigh + shin + TILE_SIZE / 2.0);
 
-            // Running
-            rag_doll.hip.x = mid.x;
-            rag_doll.hip.y = mid.y;
+                if let Some(ground) = raycast(obstacles, origin, direction) {
+                    targets[i] = Some(Vector2::new(foot.x, ground.y - lift));
+                }
+            }
 
-            let swap = if p > 1.0 {
-                p -= 1.0;
-                true
-            } else {
-                false
-            };
+            // Lower the body until both feet can reach the ground, for example on a slope where
+            // the hull only rests on one of its corners
+            let mut drop: f64 = 0.0;
+            for target in targets.iter().filter_map(|t| *t) {
+                let dx = target.x - rag_doll.hip.x;
+                let below = ((thigh + shin) * (thigh + shin) - dx * dx).max(0.0).sqrt();
 
-            rag_doll.shoulder = rag_doll.hip + size.y / 3.0 * angle(map(p, 80.0, 80.0));
-
-            let arm_angles = [
-                map(p, 360.0 - 30.0, 360.0 - 160.0),
-                map(p, 360.0 - 160.0, 360.0 - 30.0)
-            ];
-
-            rag_doll.arm_joints = [
-                rag_doll.shoulder + size.y / 5.0 * angle(arm_angles[0]),
-                rag_doll.shoulder + size.y / 5.0 * angle(arm_angles[1])
-            ];
-            rag_doll.hands = [
-                rag_doll.arm_joints[0] + size.y / 5.0 * angle(arm_angles[0] + 70.0),
-                rag_doll.arm_joints[1] + size.y / 5.0 * angle(arm_angles[1] + 70.0)
-            ];
-
-            let leg_angles = [
-                map(p, 360.0 - 60.0, 360.0 - 140.0),
-                map(p, 360.0 - 140.0, 360.0 - 60.0)
-            ];
-
-            rag_doll.leg_joints = [
-                rag_doll.hip + size.y / 4.0 * angle(leg_angles[0]),
-                rag_doll.hip + size.y / 4.0 * angle(leg_angles[1]),
-            ];
-
-            rag_doll.feet = [
-                rag_doll.leg_joints[0] + size.y / 4.0 * angle(leg_angles[0] - map(p, 0.0, 60.0)),
-                rag_doll.leg_joints[1] + size.y / 4.0 * angle(leg_angles[1] - map(p, 60.0, 0.0)),
-            ];
-
-            if swap {
-                rag_doll.arm_joints.swap(0, 1);
-                rag_doll.hands.swap(0, 1);
-                rag_doll.leg_joints.swap(0, 1);
-                rag_doll.feet.swap(0, 1);
+                drop = drop.max(target.y - rag_doll.hip.y - below);
             }
-        } else {
-            let angle = |a: f64| {
-                angle(if self.velocity.x > 0.0 { a } else { 180.0 - a })
-            };
+            rag_doll.translate(Vector2::new(0.0, drop.min(HEIGHT / 3.0)));
+
+            for i in 0..2 {
+                if let Some(target) = targets[i] {
+                    let (knee, foot) = reach(rag_doll.hip, rag_doll.leg_joints[i], target, thigh, shin);
+                    rag_doll.leg_joints[i] = knee;
+                    rag_doll.feet[i] = foot;
+                }
+            }
+        }
+
+        if let (PlayerState::WallSliding, Some(normal)) = (self.state, self.wall_normal) {
+            let upper_arm = HEIGHT / 5.0;
+            let forearm = HEIGHT / 5.0;
+
+            for i in 0..2 {
+                let hand = rag_doll.hands[i];
+
+                let origin = Vector2::new(rag_doll.shoulder.x, hand.y);
+                let direction = Vector2::new(-normal.x.signum() * (upper_arm + forearm), 0.0);
+
+                if let Some(wall) = raycast(obstacles, origin, direction) {
+                    let (elbow, hand) = reach(rag_doll.shoulder, rag_doll.arm_joints[i], wall, upper_arm, forearm);
+                    rag_doll.arm_joints[i] = elbow;
+                    rag_doll.hands[i] = hand;
+                }
+            }
+        }
+    }
 
-            // Falling/Jumping
-            let vy = {
-                let max = -50.0;
-                let min = 75.0;
-                let v = (self.velocity.y - min) / (max - min);
-                if v > 1.0 { 1.0 } else if v < 0.0 { 0.0 } else { v }
-            };
 
-            rag_doll.hip.x = mid.x;
-            rag_doll.hip.y = mid.y;
-
-            rag_doll.shoulder = rag_doll.hip + size.y / 3.0 * angle(map(vy, 100.00, 70.0));
-
-            rag_doll.arm_joints = [
-                rag_doll.shoulder + size.y / 5.0 * angle(map(vy, 0.0, -60.0)),
-                rag_doll.shoulder + size.y / 5.0 * angle(map(vy, 180.0, 210.0))
-            ];
-            rag_doll.hands = [
-                rag_doll.arm_joints[0] + size.y / 5.0 * angle(map(vy, 30.0, 50.0)),
-                rag_doll.arm_joints[1] + size.y / 5.0 * angle(map(vy, 200.0, 220.0))
-            ];
-
-            rag_doll.leg_joints = [
-                rag_doll.hip + size.y / 4.0 * angle(map(vy, -60.0, 10.0)),
-                rag_doll.hip + size.y / 4.0 * angle(map(vy, 360.0 - 10.0, 240.0)),
-            ];
-
-            rag_doll.feet = [
-                rag_doll.leg_joints[0] + size.y / 4.0 * angle(map(vy, 360.0 - 60.0, 240.0)),
-                rag_doll.leg_joints[1] + size.y / 4.0 * angle(map(vy, 220.0, 240.0)),
-            ];
+    /// Returns the pose while hanging from a ledge
+    fn get_hanging_rag_doll(&self, ledge: &Ledge, mid: Vector2, height: f64) -> RagDoll {
+        let angle = |a: f64| {
+            angle(if ledge.direction == MoveDirection::Right { a } else { 180.0 - a })
         };
 
+        let mut rag_doll = RagDoll::from_points(&[mid; RagDoll::POINT_COUNT]);
+
+        rag_doll.shoulder = rag_doll.hip + height / 3.0 * angle(95.0);
+
+        rag_doll.hands = [
+            ledge.grip + height / 12.0 * angle(180.0),
+            ledge.grip + height / 24.0 * angle(180.0),
+        ];
+        rag_doll.arm_joints = [
+            (rag_doll.shoulder + rag_doll.hands[0]) / 2.0 + height / 10.0 * angle(180.0),
+            (rag_doll.shoulder + rag_doll.hands[1]) / 2.0 + height / 12.0 * angle(180.0),
+        ];
+
+        rag_doll.leg_joints = [
+            rag_doll.hip + height / 4.0 * angle(280.0),
+            rag_doll.hip + height / 4.0 * angle(265.0),
+        ];
+
+        rag_doll.feet = [
+            rag_doll.leg_joints[0] + height / 4.0 * angle(265.0),
+            rag_doll.leg_joints[1] + height / 4.0 * angle(255.0),
+        ];
 
         rag_doll
     }
@@ -363,22 +645,44 @@ impl Player {
 
 
     /// Handle any commands
-    fn handle_commands(&mut self, dt: f64) {
+    fn handle_commands(&mut self, dt: f64, movement: &MovementConfig) {
         self.move_direction = None;
 
         self.sliding = false;
 
-        let commands = self.commands.clone();
+        self.aim = 0.0;
+        self.dash_requested = false;
+        self.drop_requested = false;
+        self.crouch_held = false;
+
+        // Commands are applied in a fixed order, no matter the order they were submitted in
+        let mut commands = self.commands.clone();
+        commands.sort();
+        commands.dedup();
+
         for command in commands {
             match command {
-                PlayerCommand::MoveLeft => { self.move_direction(MoveDirection::Left, dt); }
-                PlayerCommand::MoveRight => { self.move_direction(MoveDirection::Right, dt); }
+                PlayerCommand::MoveLeft => { self.move_direction(MoveDirection::Left, dt, movement); }
+                PlayerCommand::MoveRight => { self.move_direction(MoveDirection::Right, dt, movement); }
 
-                PlayerCommand::Jump => { self.jumping = true; }
-                PlayerCommand::StopJump => { self.jumping = false; }
+                PlayerCommand::Jump => {
+                    self.jump_buffer = Some(0);
+                    self.jump_held = true;
+                }
+                PlayerCommand::StopJump => { self.jump_held = false; }
 
-                PlayerCommand::Drop => { self.wall_normal = None; }
+                PlayerCommand::Drop => {
+                    self.wall_normal = None;
+                    self.last_wall = None;
+                    self.drop_requested = true;
+                }
                 PlayerCommand::Slide => {self.sliding = true; }
+                PlayerCommand::Crouch => { self.crouch_held = true; }
+
+                PlayerCommand::AimUp => { self.aim -= 1.0; }
+                PlayerCommand::AimDown => { self.aim += 1.0; }
+
+                PlayerCommand::Dash => { self.dash_requested = true; }
             }
         }
 
@@ -386,8 +690,336 @@ impl Player {
     }
 
 
+    /// Forgets the pending jump and where it could have been made from
+    fn consume_jump(&mut self) {
+        self.jump_buffer = None;
+
+        self.last_ground = None;
+        self.last_wall = None;
+        self.wall_normal = None;
+    }
+
+
+    /// Crouches while crouch is held on the ground, and stands back up once it is released and there
+    /// is room to do so
+    fn update_crouch(&mut self, movement: &MovementConfig, obstacles: &[&Collide<ConvexHull>]) {
+        if self.crouch_held && !self.crouching && self.ground_normal.is_some() {
+            self.set_height(movement.crouch_height);
+            self.crouching = true;
+        } else if !self.crouch_held && self.crouching {
+            let bounds = self.collision.bounding_box();
+
+            if is_free(obstacles, bounds.left + 0.5, bounds.right - 0.5, bounds.bottom - HEIGHT, bounds.bottom - 0.5) {
+                self.set_height(HEIGHT);
+                self.crouching = false;
+            }
+        }
+    }
+
+
+    /// Changes the height of the player's hull, keeping the feet in place
+    fn set_height(&mut self, height: f64) {
+        let bounds = self.collision.bounding_box();
+        let center = Vector2::new(self.center.x, bounds.bottom - height / 2.0);
+
+        self.collision = ConvexHull::from_points(&[
+            Vector2::new(center.x - WIDTH / 2.0, center.y - height / 2.0),
+            Vector2::new(center.x + WIDTH / 2.0, center.y - height / 2.0),
+            Vector2::new(center.x + WIDTH / 2.0, center.y + height / 2.0),
+            Vector2::new(center.x - WIDTH / 2.0, center.y + height / 2.0),
+        ]);
+        self.center = center;
+    }
+
+
+    /// Returns true while the player's hull is lowered
+    pub fn is_crouching(&self) -> bool {
+        self.crouching
+    }
+
+
+    /// Looks for the corner of a tile just in front of the top of the player to grab on to, while
+    /// falling towards a wall. Returns true if a ledge was grabbed.
+    fn check_ledge_grab(&mut self, movement: &MovementConfig, tile_map: &TileMap, obstacles: &[&Collide<ConvexHull>]) -> bool {
+        if self.ground_normal.is_some() || self.velocity.y < 0.0 || self.crouching {
+            return false;
+        }
+
+        let direction = match self.wall_normal {
+            Some(normal) => if normal.x > 0.0 { MoveDirection::Left } else { MoveDirection::Right },
+            None => match self.move_direction {
+                Some(direction) => direction,
+                None => return false,
+            }
+        };
+        let d = if direction == MoveDirection::Left { -1.0 } else { 1.0 };
+
+        let bounds = self.collision.bounding_box();
+        let width = bounds.right - bounds.left;
+        let height = bounds.bottom - bounds.top;
+
+        let front = if d > 0.0 { bounds.right } else { bounds.left };
+        let reach = front + d * movement.ledge_reach;
+
+        // The highest tile within reach in front of the top of the player is the one that can be
+        // grabbed. If its corner is above the top of the player, the wall is too high to grab.
+        let highest = tile_map.world_to_tile(Vector2::new(reach, bounds.top - 2.0));
+        let lowest = tile_map.world_to_tile(Vector2::new(reach, bounds.top + movement.ledge_grab_depth));
+
+        let tile = (highest.y..lowest.y + 1)
+            .map(|y| Vector2i::new(highest.x, y))
+            .filter_map(|pos| tile_map.get_tile(pos).map(|tile| (pos, tile)))
+            .next();
+
+        let (pos, tile) = if let Some(tile) = tile { tile } else { return false };
+
+        let incoming = if d > 0.0 { Direction::Right } else { Direction::Left };
+        let tile_size = tile_map.get_tile_size();
+        let corner = if let Some(corner) = tile.get_ledge(tile_size, incoming) { corner } else { return false };
+
+        let grip = Vector2::new(pos.x as f64, pos.y as f64) * tile_size + corner;
+        if grip.y <= bounds.top - 2.0 || grip.y > bounds.top + movement.ledge_grab_depth || d * (grip.x - front) < -0.5 {
+            return false;
+        }
+
+        let (wall, ledge_top) = (grip.x, grip.y);
+
+        let hang_center = Vector2::new(wall - d * width / 2.0, ledge_top + height / 2.0 - movement.ledge_hang_height);
+        let stand_center = Vector2::new(wall + d * (width / 2.0 + 1.0), ledge_top - height / 2.0 - 0.5);
+
+        // There has to be room to hang, to climb straight up and to stand on top of the ledge
+        let inset = 0.5;
+        let hang_free = is_free(obstacles,
+                                hang_center.x - width / 2.0 + inset, hang_center.x + width / 2.0 - inset,
+                                stand_center.y - height / 2.0 + inset, hang_center.y + height / 2.0 - inset);
+        let stand_free = is_free(obstacles,
+                                 stand_center.x - width / 2.0 + inset, stand_center.x + width / 2.0 - inset,
+                                 stand_center.y - height / 2.0 + inset, stand_center.y + height / 2.0 - inset);
+
+        if !hang_free || !stand_free {
+            return false;
+        }
+
+        let amount = hang_center - self.center;
+        self.translate(amount);
+
+        self.velocity = Vector2::new(0.0, 0.0);
+        self.wall_normal = None;
+        self.last_wall = None;
+        self.jump_buffer = None;
+        self.jump_start = None;
+
+        self.face_direction = direction;
+
+        self.ledge = Some(Ledge {
+            direction,
+            grip,
+            hang_center,
+            stand_center,
+            climb: None,
+        });
+
+        true
+    }
+
+
+    /// Hangs from, or climbs up, the current ledge, returns the state the player ends up in
+    fn update_ledge(&mut self, dt: f64, movement: &MovementConfig, tile_map: &TileMap, obstacles: &[&Collide<ConvexHull>]) -> PlayerState {
+        let mut ledge = if let Some(ledge) = self.ledge.take() { ledge } else { return self.free_state(movement) };
+
+        self.velocity = Vector2::new(0.0, 0.0);
+
+        let climb = match ledge.climb {
+            // Climbing can not be stopped halfway
+            Some(ticks) => ticks + 1,
+
+            None => {
+                // Dropping lets go and starts falling straight away
+                if self.drop_requested {
+                    self.ledge_cooldown = movement.ledge_regrab_ticks;
+                    return self.update_movement(dt, movement, tile_map, obstacles);
+                }
+
+                if self.jump_buffer.is_some() {
+                    self.jump_buffer = None;
+                    0
+                } else {
+                    // Pushing away from the wall lets go
+                    if let Some(direction) = self.move_direction {
+                        if direction != ledge.direction {
+                            self.ledge_cooldown = movement.ledge_regrab_ticks;
+                            return self.free_state(movement);
+                        }
+                    }
+
+                    self.ledge = Some(ledge);
+                    return PlayerState::Hanging;
+                }
+            }
+        };
+
+        // First climb straight up, then over the edge
+        let half = (movement.mantle_ticks as f64 / 2.0).max(1.0);
+        let t = climb as f64;
+
+        let target = if t < half {
+            let f = t / half;
+            Vector2::new(ledge.hang_center.x, ledge.hang_center.y + (ledge.stand_center.y - ledge.hang_center.y) * f)
+        } else {
+            let f = ((t - half) / half).min(1.0);
+            Vector2::new(ledge.hang_center.x + (ledge.stand_center.x - ledge.hang_center.x) * f, ledge.stand_center.y)
+        };
+
+        let amount = target - self.center;
+        self.translate(amount);
+
+        if t < movement.mantle_ticks as f64 {
+            ledge.climb = Some(climb);
+            self.ledge = Some(ledge);
+            PlayerState::Climbing
+        } else {
+            self.free_state(movement)
+        }
+    }
+
+
+    /// Starts a dash in the held direction, or forwards if no direction is held
+    fn start_dash(&mut self) {
+        let horizontal = match self.move_direction {
+            Some(MoveDirection::Left) => -1.0,
+            Some(MoveDirection::Right) => 1.0,
+            None => if self.aim != 0.0 { 0.0 } else if self.face_direction == MoveDirection::Left { -1.0 } else { 1.0 },
+        };
+
+        self.dash_charges -= 1;
+        self.dash = Some(Dash {
+            direction: Vector2::new(horizontal, self.aim).norm(),
+            ticks: 0,
+        });
+
+        self.wall_normal = None;
+        self.jump_buffer = None;
+        self.jump_start = None;
+    }
+
+
+    /// Moves the player at dash speed, without gravity, returns the state the player ends up in
+    fn update_dash(&mut self, dt: f64, movement: &MovementConfig, obstacles: &[&Collide<ConvexHull>]) -> PlayerState {
+        let mut dash = if let Some(dash) = self.dash.take() { dash } else { return self.free_state(movement) };
+
+        let mut remaining = dash.direction * movement.dash_speed * dt;
+
+        let mut blocked = false;
+        for _ in 0..MAX_SWEEPS {
+            if let Some((time, normal)) = self.find_impact(obstacles, remaining) {
+                self.translate(remaining * time);
+
+                // Running head first into something ends the dash, glancing hits slide along it
+                let dot = normal.dot(dash.direction);
+                if dot < -0.7 {
+                    blocked = true;
+                    break;
+                } else if dot < 0.0 {
+                    dash.direction = (dash.direction - normal * dot).norm();
+                }
+
+                remaining = dash.direction * remaining.len() * (1.0 - time);
+            } else {
+                self.translate(remaining);
+                break;
+            }
+        }
+
+        self.velocity = dash.direction * movement.dash_speed;
+
+        self.ground_normal = None;
+        self.check_collisions(obstacles);
+
+        self.update_jump_windows(movement);
+
+        dash.ticks += 1;
+        if blocked || dash.ticks >= movement.dash_ticks {
+            self.velocity = self.velocity * movement.dash_end_speed;
+            self.free_state(movement)
+        } else {
+            self.dash = Some(dash);
+            PlayerState::Dashing
+        }
+    }
+
+
+    /// Returns the first obstacle the player would hit while moving, as the fraction of the motion
+    /// made before the hit and the normal of the surface that was hit
+    fn find_impact(&self, obstacles: &[&Collide<ConvexHull>], motion: Vector2) -> Option<(f64, Vector2)> {
+        obstacles.iter().filter_map(|o| { o.sweep(&self.collision, motion) })
+            .min_by(|a, b| { a.0.partial_cmp(&b.0).unwrap() })
+    }
+
+
+    /// Gives back every dash charge
+    pub fn refill_dash(&mut self, movement: &MovementConfig) {
+        self.dash_charges = movement.dash_charges;
+    }
+
+
+    /// Returns the number of dashes left before landing
+    pub fn get_dash_charges(&self) -> u32 {
+        self.dash_charges
+    }
+
+
+    /// Returns true while dashing
+    pub fn is_dashing(&self) -> bool {
+        self.state == PlayerState::Dashing
+    }
+
+
+    /// Slows the player down when jump is released while still rising from a jump, but not so much
+    /// that they end up lower than the minimum jump height
+    fn cut_jump(&mut self, movement: &MovementConfig) {
+        if let Some(start) = self.jump_start {
+            if self.velocity.y >= 0.0 {
+                self.jump_start = None;
+            } else if !self.jump_held {
+                let remaining = movement.min_jump_height - (start - self.center.y);
+                let min_speed = if remaining > 0.0 { (2.0 * movement.rise_gravity * remaining).sqrt() } else { 0.0 };
+
+                let speed = -self.velocity.y;
+                self.velocity.y = -(speed * movement.jump_cut_factor).max(min_speed).min(speed);
+
+                self.jump_start = None;
+            }
+        }
+    }
+
+
+    /// Ages the jump buffer and the time since the ground and walls were touched, forgetting them
+    /// when they fall outside their windows
+    fn update_jump_windows(&mut self, movement: &MovementConfig) {
+        let age = |window: Option<(Vector2, u32)>, limit: u32| {
+            window.and_then(|(normal, ticks)| {
+                if ticks + 1 > limit { None } else { Some((normal, ticks + 1)) }
+            })
+        };
+
+        self.last_ground = match self.ground_normal {
+            Some(normal) => Some((normal, 0)),
+            None => age(self.last_ground, movement.coyote_ticks),
+        };
+
+        self.last_wall = match self.wall_normal {
+            Some(normal) => Some((normal, 0)),
+            None => age(self.last_wall, movement.wall_coyote_ticks),
+        };
+
+        self.jump_buffer = self.jump_buffer.and_then(|ticks| {
+            if ticks + 1 > movement.jump_buffer_ticks { None } else { Some(ticks + 1) }
+        });
+    }
+
+
     /// Make the player move in a direction
-    fn move_direction(&mut self, direction: MoveDirection, dt: f64) {
+    fn move_direction(&mut self, direction: MoveDirection, dt: f64, movement: &MovementConfig) {
         if self.wall_normal.is_none() {
             let plane = if let Some(normal) = self.ground_normal {
                 Vector2::new(-normal.y, normal.x)
@@ -396,7 +1028,16 @@ impl Player {
             };
             let delta = if direction == MoveDirection::Left { -plane } else { plane };
 
-            self.velocity += delta * 300.0 * dt * if self.ground_normal.is_some() { 1.0 } else { 0.75 };
+            let control = if self.ground_normal.is_none() {
+                movement.air_control
+            } else if self.crouching {
+                // Sliding keeps its speed, but can not be sped up
+                if self.velocity.len() > movement.slide_min_speed { 0.0 } else { movement.crouch_control }
+            } else {
+                1.0
+            };
+
+            self.velocity += delta * movement.run_acceleration * dt * control;
 
             if self.move_direction.is_none() {
                 self.move_direction = Some(direction);
@@ -410,10 +1051,36 @@ impl Player {
     }
 
 
-    /// Check for and resolve any collisions
-    fn check_collisions(&mut self, obstacles: &[&Collide<ConvexHull>]) {
+    /// Moves the player until it hits something, then slides along the surface that was hit for the
+    /// rest of the motion, so that fast movement can not pass through thin walls
+    fn move_and_collide(&mut self, amount: Vector2, obstacles: &[&Collide<ConvexHull>]) {
         self.ground_normal = None;
 
+        let mut remaining = amount;
+        for _ in 0..MAX_SWEEPS {
+            if let Some((time, normal)) = self.find_impact(obstacles, remaining) {
+                self.translate(remaining * time);
+
+                self.collision_response(normal);
+
+                remaining = remaining * (1.0 - time);
+
+                let dot = remaining.dot(normal);
+                if dot < 0.0 {
+                    remaining = remaining - normal * dot;
+                }
+            } else {
+                self.translate(remaining);
+                break;
+            }
+        }
+
+        self.check_collisions(obstacles);
+    }
+
+
+    /// Check for and resolve any overlaps left after moving
+    fn check_collisions(&mut self, obstacles: &[&Collide<ConvexHull>]) {
         let mut final_normal = None;
 
         let mut remaining_iterations = 100;
@@ -510,4 +1177,353 @@ impl Player {
     pub fn get_center(&self) -> Vector2 {
         self.center
     }
+
+
+    /// Returns where the player is drawn: the center, or the hip of the rag doll once dead
+    pub fn get_drawn_center(&self) -> Vector2 {
+        if self.is_dead() {
+            self.rag_doll.hip
+        } else {
+            self.center
+        }
+    }
+
+
+    /// Returns the player's velocity
+    pub fn get_velocity(&self) -> Vector2 {
+        self.velocity
+    }
+
+
+    /// Returns the bounding box of the player's collision hull
+    pub fn get_bounding_box(&self) -> AABB {
+        self.collision.bounding_box()
+    }
+
+
+    /// Returns true if the player is standing on the ground
+    pub fn is_grounded(&self) -> bool {
+        self.ground_normal.is_some()
+    }
+
+
+    /// Returns true if the player is clinging to a wall
+    pub fn is_on_wall(&self) -> bool {
+        self.wall_normal.is_some()
+    }
+
+
+    /// Returns true if the player is hanging from, or climbing up, a ledge
+    pub fn is_hanging(&self) -> bool {
+        self.state == PlayerState::Hanging || self.state == PlayerState::Climbing
+    }
+}
+
+
+impl PlayerState {
+    /// Returns true for the states where the player stands on the ground
+    fn is_on_ground(self) -> bool {
+        match self {
+            PlayerState::Grounded | PlayerState::Crouching | PlayerState::Sliding => true,
+            _ => false,
+        }
+    }
+}
+
+
+impl RagDoll {
+    /// Number of points that make up a pose
+    pub const POINT_COUNT: usize = 10;
+
+
+    /// Draw the limbs in a color
+    pub fn draw(&self, renderer: &mut Renderer, color: [f64; 4]) {
+        renderer.color = color;
+        renderer.line_width = 2.0;
+
+        renderer.draw_rounded_line(self.shoulder, self.hip);
+        for i in 0..2 {
+            renderer.draw_rounded_line(self.shoulder, self.arm_joints[i]);
+            renderer.draw_rounded_line(self.arm_joints[i], self.hands[i]);
+
+            renderer.draw_rounded_line(self.hip, self.leg_joints[i]);
+            renderer.draw_rounded_line(self.leg_joints[i], self.feet[i]);
+        }
+    }
+
+
+    /// Returns every limb endpoint, starting with the hip
+    pub fn get_points(&self) -> [Vector2; RagDoll::POINT_COUNT] {
+        [
+            self.hip,
+            self.shoulder,
+            self.arm_joints[0], self.arm_joints[1],
+            self.hands[0], self.hands[1],
+            self.leg_joints[0], self.leg_joints[1],
+            self.feet[0], self.feet[1],
+        ]
+    }
+
+
+    /// Creates a rag doll in an animated pose, with the torso a third of a length long and every
+    /// other limb a fifth or a fourth
+    pub fn from_pose(hip: Vector2, pose: &Pose, length: f64, facing_right: bool) -> RagDoll {
+        let angle = |a: f64| {
+            angle(if facing_right { a } else { 180.0 - a })
+        };
+
+        let shoulder = hip + length / 3.0 * angle(pose.torso);
+        let arm_joints = [
+            shoulder + length / 5.0 * angle(pose.arms[0]),
+            shoulder + length / 5.0 * angle(pose.arms[1]),
+        ];
+        let leg_joints = [
+            hip + length / 4.0 * angle(pose.thighs[0]),
+            hip + length / 4.0 * angle(pose.thighs[1]),
+        ];
+
+        RagDoll {
+            shoulder,
+            arm_joints,
+            hands: [
+                arm_joints[0] + length / 5.0 * angle(pose.forearms[0]),
+                arm_joints[1] + length / 5.0 * angle(pose.forearms[1]),
+            ],
+
+            hip,
+            leg_joints,
+            feet: [
+                leg_joints[0] + length / 4.0 * angle(pose.shins[0]),
+                leg_joints[1] + length / 4.0 * angle(pose.shins[1]),
+            ],
+        }
+    }
+
+
+    /// Moves every limb by an amount
+    pub fn translate(&mut self, amount: Vector2) {
+        self.shoulder += amount;
+        self.hip += amount;
+
+        for i in 0..2 {
+            self.arm_joints[i] += amount;
+            self.hands[i] += amount;
+            self.leg_joints[i] += amount;
+            self.feet[i] += amount;
+        }
+    }
+
+
+    /// Returns the pose a fraction of the way to another pose
+    pub fn lerp(&self, other: &RagDoll, fraction: f64) -> RagDoll {
+        let mut points = self.get_points();
+        for (point, target) in points.iter_mut().zip(other.get_points().iter()) {
+            *point += (*target - *point) * fraction;
+        }
+
+        RagDoll::from_points(&points)
+    }
+
+
+    /// Creates a pose from points in the order returned by get_points
+    pub fn from_points(points: &[Vector2; RagDoll::POINT_COUNT]) -> RagDoll {
+        RagDoll {
+            hip: points[0],
+            shoulder: points[1],
+            arm_joints: [points[2], points[3]],
+            hands: [points[4], points[5]],
+            leg_joints: [points[6], points[7]],
+            feet: [points[8], points[9]],
+        }
+    }
+}
+
+
+impl PlayerCommand {
+    /// Returns the command with a name, as written by Display
+    pub fn from_name(name: &str) -> Option<PlayerCommand> {
+        Some(match name {
+            "MoveLeft" => PlayerCommand::MoveLeft,
+            "MoveRight" => PlayerCommand::MoveRight,
+
+            "Jump" => PlayerCommand::Jump,
+            "StopJump" => PlayerCommand::StopJump,
+
+            "Drop" => PlayerCommand::Drop,
+            "Slide" => PlayerCommand::Slide,
+            "Crouch" => PlayerCommand::Crouch,
+
+            "AimUp" => PlayerCommand::AimUp,
+            "AimDown" => PlayerCommand::AimDown,
+
+            "Dash" => PlayerCommand::Dash,
+
+            _ => return None
+        })
+    }
+}
+
+impl std::fmt::Display for PlayerCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", match *self {
+            PlayerCommand::MoveLeft => "MoveLeft",
+            PlayerCommand::MoveRight => "MoveRight",
+
+            PlayerCommand::Jump => "Jump",
+            PlayerCommand::StopJump => "StopJump",
+
+            PlayerCommand::Drop => "Drop",
+            PlayerCommand::Slide => "Slide",
+            PlayerCommand::Crouch => "Crouch",
+
+            PlayerCommand::AimUp => "AimUp",
+            PlayerCommand::AimDown => "AimDown",
+
+            PlayerCommand::Dash => "Dash",
+        })
+    }
+}
+
+
+impl Player {
+    /// Writes the state of the player to a hasher. Only integers and the bits of floats are
+    /// written, and enums as their index, so the hash is the same with every version of Rust. The
+    /// rag doll only affects how the player is drawn, so it is left out.
+    pub fn write_hash(&self, hasher: &mut Fnv1a) {
+        hasher.write_usize(self.collision.get_points().len());
+        for point in self.collision.get_points() {
+            hash_vector(*point, hasher);
+        }
+
+        hash_vector(self.center, hasher);
+        hash_vector(self.velocity, hasher);
+
+        hash_option(self.move_direction, hasher, |direction, hasher| hasher.write_u8(direction as u8));
+        hasher.write_u8(self.face_direction as u8);
+
+        hash_option(self.ground_normal, hasher, hash_vector);
+        hash_option(self.wall_normal, hasher, hash_vector);
+
+        for &window in [self.last_ground, self.last_wall].iter() {
+            hash_option(window, hasher, |(normal, ticks), hasher| {
+                hash_vector(normal, hasher);
+                hasher.write_u32(ticks);
+            });
+        }
+
+        hash_option(self.jump_buffer, hasher, |ticks, hasher| hasher.write_u32(ticks));
+        hash_bool(self.jump_held, hasher);
+
+        hash_option(self.jump_start, hasher, |start, hasher| hasher.write_u64(start.to_bits()));
+
+        hash_option(self.ledge.as_ref(), hasher, |ledge, hasher| {
+            hasher.write_u8(ledge.direction as u8);
+            hash_vector(ledge.grip, hasher);
+            hash_vector(ledge.hang_center, hasher);
+            hash_vector(ledge.stand_center, hasher);
+            hash_option(ledge.climb, hasher, |ticks, hasher| hasher.write_u32(ticks));
+        });
+        hasher.write_u32(self.ledge_cooldown);
+
+        hasher.write_u64(self.aim.to_bits());
+        hash_option(self.dash.as_ref(), hasher, |dash, hasher| {
+            hash_vector(dash.direction, hasher);
+            hasher.write_u32(dash.ticks);
+        });
+        hash_bool(self.dash_requested, hasher);
+        hash_bool(self.drop_requested, hasher);
+        hasher.write_u32(self.dash_charges);
+
+        hash_bool(self.crouching, hasher);
+        hash_bool(self.crouch_held, hasher);
+        hash_bool(self.sliding, hasher);
+
+        hasher.write_u8(self.state as u8);
+
+        hasher.write_usize(self.commands.len());
+        for &command in self.commands.iter() {
+            hasher.write_u8(command as u8);
+        }
+    }
+}
+
+
+/// Construct unit vector with an angle, in degrees, between x axis
+fn angle(angle: f64) -> Vector2 {
+    use std::f64::consts::PI;
+    let rad = angle / 180.0 * PI;
+    Vector2::new(rad.cos(), -rad.sin())
+}
+
+
+/// Returns true if a rectangle does not overlap any obstacle
+fn is_free(obstacles: &[&Collide<ConvexHull>], left: f64, right: f64, top: f64, bottom: f64) -> bool {
+    let probe = ConvexHull::from_points(&[
+        Vector2::new(left, top),
+        Vector2::new(right, top),
+        Vector2::new(right, bottom),
+        Vector2::new(left, bottom),
+    ]);
+
+    !obstacles.iter().any(|o| o.overlap(&probe).is_some())
+}
+
+
+/// Returns the first point a ray hits on any obstacle
+fn raycast(obstacles: &[&Collide<ConvexHull>], origin: Vector2, direction: Vector2) -> Option<Vector2> {
+    let mut best: Option<f64> = None;
+
+    for obstacle in obstacles.iter() {
+        if let Some((time, _)) = obstacle.raycast(origin, direction) {
+            if best.map_or(true, |earliest| time < earliest) {
+                best = Some(time);
+            }
+        }
+    }
+
+    best.map(|time| origin + direction * time)
+}
+
+
+/// Bends a limb made of two bones, from a root through a joint, so that its end reaches a target,
+/// or points straight at it if it is too far away. The joint bends to the same side it did
+/// before. Returns the new joint and end.
+fn reach(root: Vector2, joint: Vector2, target: Vector2, upper: f64, lower: f64) -> (Vector2, Vector2) {
+    let delta = target - root;
+    let distance = delta.len().min(upper + lower).max((upper - lower).abs());
+
+    if distance <= 0.0 {
+        return (joint, target);
+    }
+
+    let direction = delta.norm();
+    let side = Vector2::new(-direction.y, direction.x);
+    let side = if side.dot(joint - root) < 0.0 { -side } else { side };
+
+    // Law of cosines, the distance along the limb to the joint and how far it sticks out
+    let along = (upper * upper - lower * lower + distance * distance) / (2.0 * distance);
+    let out = (upper * upper - along * along).max(0.0).sqrt();
+
+    (root + direction * along + side * out, root + direction * distance)
+}
+
+
+/// Hashes the exact bit patterns of a vector
+fn hash_vector(vector: Vector2, hasher: &mut Fnv1a) {
+    hasher.write_u64(vector.x.to_bits());
+    hasher.write_u64(vector.y.to_bits());
+}
+
+fn hash_bool(value: bool, hasher: &mut Fnv1a) {
+    hasher.write_u8(value as u8);
+}
+
+/// Hashes whether there is a value, followed by the value
+fn hash_option<T, F: FnOnce(T, &mut Fnv1a)>(option: Option<T>, hasher: &mut Fnv1a, hash_value: F) {
+    if let Some(value) = option {
+        hasher.write_u8(1);
+        hash_value(value, hasher);
+    } else {
+        hasher.write_u8(0);
+    }
 }
@@ -0,0 +1,411 @@
+use std::any::Any;
+
+use trap::{Vector2, Vector2i};
+
+use rax::Game;
+use rax::Renderer;
+use rax::actions::ActionMap;
+use rax::scene::Scene;
+use rax::input::Input;
+
+use frame_counter::FrameCounter;
+
+use controls::{self, Controls};
+use player::{PlayerCommand, PlayerEvent, RagDoll};
+use tile_map::TileMap;
+use simulation::{self, Simulation};
+use runplusplus::RESPAWN_DELAY_TICKS;
+use movement_config::{MovementConfigWatcher, MOVEMENT_CONFIG_PATH};
+use animation::{AnimationWatcher, ANIMATION_DIRECTORY};
+
+use ::TILE_SIZE;
+
+
+/// Fewest and most players in a race
+pub const MIN_PLAYERS: usize = 2;
+pub const MAX_PLAYERS: usize = 4;
+
+/// The color each player is drawn in
+const COLORS: [[f64; 4]; MAX_PLAYERS] = [
+    [1.0, 1.0, 1.0, 1.0],
+    [1.0, 0.4, 0.3, 1.0],
+    [0.4, 0.8, 1.0, 1.0],
+    [1.0, 0.9, 0.3, 1.0],
+];
+
+/// How often the movement config file is checked for changes, in seconds
+const CONFIG_POLL_INTERVAL: f64 = 0.5;
+
+/// Number of ticks the race goes on for after the winner reaches the exit
+const FINISH_DELAY_TICKS: u64 = 3 * simulation::TICK_RATE;
+
+/// Space kept free between the players and the edge of the screen, in pixels
+const CAMERA_MARGIN: f64 = 150.0;
+
+/// How far the camera zooms out to keep everyone in view
+const MIN_ZOOM: f64 = 0.25;
+
+
+/// Several players on one keyboard racing through the same level, the first to reach the exit wins
+pub struct Race {
+    frame_counter: FrameCounter,
+
+    running: bool,
+
+    // Keys and mouse buttons bound to actions, shared by every player
+    actions: ActionMap,
+    window_size: Vector2i,
+
+    // Every player runs in their own simulation, so they do not get in each other's way
+    racers: Vec<Racer>,
+
+    // Number of ticks since the race was started
+    tick: u64,
+
+    // The player that reached the exit first, and the tick they did
+    winner: Option<(usize, u64)>,
+
+    camera_center: Vector2,
+    camera_zoom: f64,
+
+    // Where the camera was before the last tick
+    previous_camera_center: Vector2,
+    previous_camera_zoom: f64,
+
+    // Movement parameters, reloaded whenever the file changes
+    movement: MovementConfigWatcher,
+    config_poll_timer: f64,
+
+    // The player's animations, reloaded whenever a file changes
+    animations: AnimationWatcher,
+}
+
+
+/// One of the players in a race
+struct Racer {
+    simulation: Simulation,
+
+    controls: Controls,
+    color: [f64; 4],
+
+    // Commands from key presses, submitted during the next tick
+    pending_commands: Vec<PlayerCommand>,
+
+    // The tick of the race the player died during
+    death_tick: Option<u64>,
+
+    // The tick of the race the player reached the exit during
+    finish_tick: Option<u64>,
+
+    // The player's pose before the last tick
+    previous_pose: Option<RagDoll>,
+}
+
+
+impl Race {
+    /// Start a race through a level file
+    pub fn open(level_path: &str, players: usize) -> Self {
+        let tile_map = match TileMap::open(level_path, TILE_SIZE) {
+            Ok(tile_map) => {
+                println!("Loaded map!");
+                tile_map
+            }
+            Err(e) => {
+                println!("Failed to load map {}: {}", level_path, e);
+                TileMap::new(64.0)
+            }
+        };
+
+        Race::with_tile_map(tile_map, players)
+    }
+
+
+    /// Start a race through a tile map
+    pub fn with_tile_map(tile_map: TileMap, players: usize) -> Self {
+        let players = players.max(MIN_PLAYERS).min(MAX_PLAYERS);
+
+        let movement = MovementConfigWatcher::new(MOVEMENT_CONFIG_PATH);
+        let animations = AnimationWatcher::new(ANIMATION_DIRECTORY);
+
+        let racers = (0..players).map(|i| {
+            let mut simulation = Simulation::new(tile_map.clone());
+            simulation.set_movement_config(movement.get_config());
+            simulation.set_animations(animations.get_animations().clone());
+
+            Racer {
+                simulation,
+
+                controls: controls::PRESETS[i],
+                color: COLORS[i],
+
+                pending_commands: Vec::new(),
+
+                death_tick: None,
+                finish_tick: None,
+
+                previous_pose: None,
+            }
+        }).collect();
+
+        let mut race = Race {
+            frame_counter: FrameCounter::new(),
+
+            running: true,
+
+            actions: controls::load_bindings(),
+            window_size: Vector2i::new(1, 1),
+
+            racers,
+
+            tick: 0,
+            winner: None,
+
+            camera_center: Vector2::new(0.0, 0.0),
+            camera_zoom: 1.0,
+
+            previous_camera_center: Vector2::new(0.0, 0.0),
+            previous_camera_zoom: 1.0,
+
+            movement,
+            config_poll_timer: 0.0,
+
+            animations,
+        };
+
+        race.restart();
+        race.camera_center = race.players_center().0;
+        race.previous_camera_center = race.camera_center;
+        race
+    }
+
+
+    /// Returns the number of players in the race
+    pub fn get_player_count(&self) -> usize {
+        self.racers.len()
+    }
+
+
+    /// Moves every player back to the start of the level
+    fn restart(&mut self) {
+        for racer in self.racers.iter_mut() {
+            racer.simulation.restart();
+            racer.pending_commands.clear();
+            racer.death_tick = None;
+            racer.finish_tick = None;
+        }
+
+        self.tick = 0;
+        self.winner = None;
+
+        println!("Race with {} players started!", self.racers.len());
+    }
+
+
+    /// Advances every player by one tick
+    fn tick(&mut self, input: &Input) {
+        self.tick += 1;
+
+        self.previous_camera_center = self.camera_center;
+        self.previous_camera_zoom = self.camera_zoom;
+
+        for (i, racer) in self.racers.iter_mut().enumerate() {
+            racer.previous_pose = Some(racer.simulation.get_player().get_pose().clone());
+
+            // Players that made it wait at the exit for the others
+            if racer.finish_tick.is_some() {
+                continue;
+            }
+
+            let mut commands = racer.pending_commands.split_off(0);
+            commands.extend(racer.controls.held_commands(&self.actions, input));
+
+            racer.simulation.step(&commands);
+
+            for event in racer.simulation.get_player().get_events() {
+                if *event == PlayerEvent::Died {
+                    racer.death_tick = Some(self.tick);
+                }
+            }
+
+            if racer.simulation.is_at_exit(racer.simulation.get_state()) {
+                racer.finish_tick = Some(self.tick);
+
+                let time = self.tick as f64 * simulation::TICK_TIME;
+                if self.winner.is_none() {
+                    println!("Player {} wins in {:.2} s!", i + 1, time);
+                    self.winner = Some((i, self.tick));
+                } else {
+                    println!("Player {} finished in {:.2} s", i + 1, time);
+                }
+            }
+
+            // Only the player that died starts over, the race goes on
+            if let Some(tick) = racer.death_tick {
+                if self.tick >= tick + RESPAWN_DELAY_TICKS {
+                    racer.simulation.restart();
+                    racer.death_tick = None;
+                }
+            }
+        }
+
+        let everyone_finished = self.racers.iter().all(|racer| racer.finish_tick.is_some());
+        if let Some((_, tick)) = self.winner {
+            if everyone_finished || self.tick >= tick + FINISH_DELAY_TICKS {
+                self.restart();
+            }
+        }
+
+        // Zoom out as far as needed to keep everyone on the screen
+        let (center, size) = self.players_center();
+        let zoom = ((self.window_size.x as f64 - 2.0 * CAMERA_MARGIN) / size.x)
+            .min((self.window_size.y as f64 - 2.0 * CAMERA_MARGIN) / size.y)
+            .min(1.0)
+            .max(MIN_ZOOM);
+
+        self.camera_center += (center - self.camera_center) * simulation::TICK_TIME * 4.0;
+        self.camera_zoom += (zoom - self.camera_zoom) * simulation::TICK_TIME * 4.0;
+    }
+
+
+    /// Reacts to an action that was just started by a key or mouse button
+    fn on_action_start(&mut self, action: &str, input: &Input) {
+        if action == "RESTART" {
+            self.restart();
+        }
+
+        for racer in self.racers.iter_mut() {
+            if let Some(command) = racer.controls.start_command(action, &self.actions, input) {
+                racer.pending_commands.push(command);
+            }
+        }
+    }
+
+
+    /// Reacts to an action that was just stopped
+    fn on_action_stop(&mut self, action: &str) {
+        for racer in self.racers.iter_mut() {
+            if let Some(command) = racer.controls.stop_command(action) {
+                racer.pending_commands.push(command);
+            }
+        }
+    }
+
+
+    /// Returns the center and size of the box around every player, players that are dead are left
+    /// out unless everyone is
+    fn players_center(&self) -> (Vector2, Vector2) {
+        use std::f64::INFINITY;
+        let mut min = Vector2::new(INFINITY, INFINITY);
+        let mut max = Vector2::new(-INFINITY, -INFINITY);
+
+        let everyone_dead = self.racers.iter().all(|racer| racer.simulation.get_player().is_dead());
+
+        for racer in self.racers.iter() {
+            let player = racer.simulation.get_player();
+            if player.is_dead() && !everyone_dead {
+                continue;
+            }
+
+            let center = player.get_drawn_center();
+
+            min = Vector2::new(min.x.min(center.x), min.y.min(center.y));
+            max = Vector2::new(max.x.max(center.x), max.y.max(center.y));
+        }
+
+        ((min + max) / 2.0, max - min)
+    }
+}
+
+
+impl Game for Race {
+    fn update(&mut self, dt: f64, input: &Input) {
+        for action in self.actions.get_started(input) {
+            self.on_action_start(&action, input);
+        }
+        for action in self.actions.get_stopped(input) {
+            self.on_action_stop(&action);
+        }
+
+        self.config_poll_timer += dt;
+        if self.config_poll_timer >= CONFIG_POLL_INTERVAL {
+            self.config_poll_timer = 0.0;
+
+            if self.movement.poll() {
+                println!("Reloaded {}", MOVEMENT_CONFIG_PATH);
+                for racer in self.racers.iter_mut() {
+                    racer.simulation.set_movement_config(self.movement.get_config());
+                }
+            }
+
+            if self.animations.poll() {
+                println!("Reloaded {}", ANIMATION_DIRECTORY);
+                for racer in self.racers.iter_mut() {
+                    racer.simulation.set_animations(self.animations.get_animations().clone());
+                }
+            }
+        }
+    }
+
+    fn fixed_update(&mut self, _dt: f64, input: &Input) {
+        // Every player advances in the same whole ticks, so no one gets ahead after a stall
+        self.tick(input);
+    }
+
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64) {
+        if let Some(fps) = self.frame_counter.tick() {
+            println!("FPS: {}", fps.round());
+        }
+
+        let camera_center = self.previous_camera_center + (self.camera_center - self.previous_camera_center) * alpha;
+        let camera_zoom = self.previous_camera_zoom + (self.camera_zoom - self.previous_camera_zoom) * alpha;
+
+        renderer.set_center(camera_center);
+        renderer.set_zoom(camera_zoom);
+
+        renderer.clear(0.2, 0.2, 0.2);
+
+        let tile_map = self.racers[0].simulation.get_tile_map();
+
+        renderer.color = [0.03, 0.03, 0.03, 1.0];
+        tile_map.draw_shadows(renderer, camera_center);
+
+        tile_map.draw(renderer);
+
+        // Every player has their own dash pickups, a pickup is only faded while it is respawning
+        // for everyone
+        for (i, pickup) in tile_map.get_dash_pickups().iter().enumerate() {
+            let available = self.racers.iter().any(|racer| racer.simulation.get_state().pickup_timers[i] == 0);
+
+            renderer.color = if available { [0.3, 0.9, 1.0, 0.8] } else { [0.3, 0.9, 1.0, 0.15] };
+            tile_map.draw_dash_pickup(renderer, *pickup);
+        }
+
+        // The winner is drawn last, on top of everyone else
+        let winner = self.winner.map(|(i, _)| i);
+        let mut order: Vec<usize> = (0..self.racers.len()).collect();
+        order.sort_by_key(|&i| Some(i) == winner);
+
+        for i in order {
+            let racer = &self.racers[i];
+            let pose = racer.simulation.get_player().get_pose();
+            let previous_pose = racer.previous_pose.as_ref().unwrap_or(pose);
+
+            previous_pose.lerp(pose, alpha).draw(renderer, racer.color);
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn on_size_change(&mut self, width: u64, height: u64) {
+        self.window_size = Vector2i::new(width as i64, height as i64);
+    }
+}
+
+
+impl Scene for Race {
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
@@ -0,0 +1,323 @@
+use std::fs::File;
+use std::path::Path;
+
+use super::{KeyCode, MouseButton};
+use super::input::Input;
+
+
+/// A key or mouse button that an action can be bound to
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+
+/// Maps keys and mouse buttons to named actions, which are looked up in the input of a frame
+#[derive(Clone)]
+pub struct ActionMap {
+    // Every action with an input bound to it, an action can appear more than once
+    bindings: Vec<(String, Binding)>,
+}
+
+
+impl ActionMap {
+    /// Create a map without any bindings
+    pub fn new() -> ActionMap {
+        ActionMap {
+            bindings: Vec::new(),
+        }
+    }
+
+
+    /// Open a bindings file
+    pub fn open<P: AsRef<Path>>(path: P) -> Option<ActionMap> {
+        if let Ok(mut file) = File::open(path) {
+            use std::io::Read;
+
+            let mut string = String::new();
+            if file.read_to_string(&mut string).is_err() {
+                return None;
+            }
+
+            ActionMap::from_str(&string)
+        } else {
+            None
+        }
+    }
+
+
+    /// Parses lines of an action followed by the inputs bound to it, lines starting with '#' are
+    /// comments. An input can only be bound to one action.
+    pub fn from_str(text: &str) -> Option<ActionMap> {
+        let mut map = ActionMap::new();
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+
+            let action = match words.next() {
+                Some(action) if !action.starts_with('#') => action,
+                _ => continue,
+            };
+
+            let mut bound = false;
+            for name in words {
+                if let Some(binding) = Binding::from_name(name) {
+                    // An input starts every action bound to it, which is never what was meant
+                    if let Some(&(ref other, _)) = map.bindings.iter().find(|&&(ref other, bound)| bound == binding && other != action) {
+                        println!("{}: '{}' is already bound to {}!", action, name, other);
+                        return None;
+                    }
+
+                    map.bind(action, binding);
+                    bound = true;
+                } else {
+                    println!("{}: unknown key or mouse button '{}'", action, name);
+                    return None;
+                }
+            }
+
+            if !bound {
+                println!("{}: expected a key or mouse button!", action);
+                return None;
+            }
+        }
+
+        Some(map)
+    }
+
+
+    /// Binds another input to an action
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let entry = (action.to_owned(), binding);
+        if !self.bindings.contains(&entry) {
+            self.bindings.push(entry);
+        }
+    }
+
+
+    /// Returns every input bound to an action
+    pub fn get_bindings(&self, action: &str) -> Vec<Binding> {
+        self.bindings.iter()
+            .filter(|&&(ref name, _)| name == action)
+            .map(|&(_, binding)| binding)
+            .collect()
+    }
+
+
+    /// Returns true if any input bound to an action is held down
+    pub fn is_active(&self, action: &str, input: &Input) -> bool {
+        self.bindings.iter().any(|&(ref name, binding)| name == action && binding.is_down(input))
+    }
+
+
+    /// Returns true if an action was started since the last frame, by pressing an input bound to
+    /// it while no other input bound to it was already held
+    pub fn was_started(&self, action: &str, input: &Input) -> bool {
+        let pressed = self.bindings.iter()
+            .any(|&(ref name, binding)| name == action && binding.was_pressed(input));
+
+        // Inputs released this frame were held before it too
+        let held_before = self.bindings.iter().any(|&(ref name, binding)| {
+            name == action && (binding.is_down(input) || binding.was_released(input)) && !binding.was_pressed(input)
+        });
+
+        pressed && !held_before
+    }
+
+
+    /// Returns true if an action was stopped since the last frame, by releasing the last input
+    /// bound to it that was held
+    pub fn was_stopped(&self, action: &str, input: &Input) -> bool {
+        let released = self.bindings.iter()
+            .any(|&(ref name, binding)| name == action && binding.was_released(input));
+
+        released && !self.is_active(action, input)
+    }
+
+
+    /// Returns every action that was started since the last frame
+    pub fn get_started(&self, input: &Input) -> Vec<String> {
+        self.get_actions_where(|action| self.was_started(action, input))
+    }
+
+    /// Returns every action that was stopped since the last frame
+    pub fn get_stopped(&self, input: &Input) -> Vec<String> {
+        self.get_actions_where(|action| self.was_stopped(action, input))
+    }
+
+
+    /// Returns every bound action that passes a test, once each
+    fn get_actions_where<F: Fn(&str) -> bool>(&self, test: F) -> Vec<String> {
+        let mut actions: Vec<String> = Vec::new();
+
+        for &(ref action, _) in self.bindings.iter() {
+            if !actions.contains(action) && test(action) {
+                actions.push(action.clone());
+            }
+        }
+
+        actions
+    }
+}
+
+
+impl Binding {
+    /// Returns the binding with a name, keys are named as in KeyCode and mouse buttons are
+    /// MouseLeft, MouseRight and MouseMiddle
+    pub fn from_name(name: &str) -> Option<Binding> {
+        match name {
+            "MouseLeft" => Some(Binding::Mouse(MouseButton::Left)),
+            "MouseRight" => Some(Binding::Mouse(MouseButton::Right)),
+            "MouseMiddle" => Some(Binding::Mouse(MouseButton::Middle)),
+
+            name => key_from_name(name).map(Binding::Key),
+        }
+    }
+
+
+    /// Returns true if the input is held down
+    fn is_down(self, input: &Input) -> bool {
+        match self {
+            Binding::Key(key) => input.is_down(key),
+            Binding::Mouse(button) => input.is_mouse_down(button),
+        }
+    }
+
+    /// Returns true if the input was pressed since the last frame
+    fn was_pressed(self, input: &Input) -> bool {
+        match self {
+            Binding::Key(key) => input.was_pressed_this_frame(key),
+            Binding::Mouse(button) => input.was_mouse_pressed_this_frame(button),
+        }
+    }
+
+    /// Returns true if the input was released since the last frame
+    fn was_released(self, input: &Input) -> bool {
+        match self {
+            Binding::Key(key) => input.was_released_this_frame(key),
+            Binding::Mouse(button) => input.was_mouse_released_this_frame(button),
+        }
+    }
+}
+
+
+macro_rules! key_names {
+    ($($key:ident),*) => {
+        /// Returns the key with a name, as written in KeyCode
+        fn key_from_name(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Left, Up, Right, Down, Back, Return, Space, Tab,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    Add, Subtract, Comma, Period, Minus, Equals, Slash,
+    LAlt, LControl, LShift, RAlt, RControl, RShift,
+    Delete, Home, End, PageUp, PageDown, Insert
+);
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::event::Event;
+
+    fn map() -> ActionMap {
+        ActionMap::from_str("# Movement\nJUMP Space W\n\nFIRE MouseLeft").unwrap()
+    }
+
+    /// Starts a frame and handles its events
+    fn frame(input: &mut Input, events: &[Event]) {
+        input.begin_frame();
+        for event in events {
+            input.handle(*event);
+        }
+    }
+
+    #[test]
+    fn press_and_release() {
+        let map = map();
+        let mut input = Input::new();
+
+        frame(&mut input, &[Event::KeyPressed(KeyCode::Space)]);
+        assert!(map.is_active("JUMP", &input));
+        assert!(map.was_started("JUMP", &input));
+        assert!(!map.was_stopped("JUMP", &input));
+        assert_eq!(map.get_started(&input), vec!["JUMP".to_owned()]);
+
+        frame(&mut input, &[]);
+        assert!(map.is_active("JUMP", &input));
+        assert!(!map.was_started("JUMP", &input));
+        assert!(!map.was_stopped("JUMP", &input));
+
+        frame(&mut input, &[Event::KeyReleased(KeyCode::Space)]);
+        assert!(!map.is_active("JUMP", &input));
+        assert!(!map.was_started("JUMP", &input));
+        assert!(map.was_stopped("JUMP", &input));
+        assert_eq!(map.get_stopped(&input), vec!["JUMP".to_owned()]);
+    }
+
+    #[test]
+    fn tap_within_a_frame() {
+        let map = map();
+        let mut input = Input::new();
+
+        frame(&mut input, &[Event::KeyPressed(KeyCode::W), Event::KeyReleased(KeyCode::W)]);
+        assert!(!map.is_active("JUMP", &input));
+        assert!(map.was_started("JUMP", &input));
+        assert!(map.was_stopped("JUMP", &input));
+    }
+
+    #[test]
+    fn switching_between_bound_inputs() {
+        let map = map();
+        let mut input = Input::new();
+
+        frame(&mut input, &[Event::KeyPressed(KeyCode::Space)]);
+        frame(&mut input, &[Event::KeyPressed(KeyCode::W)]);
+        assert!(!map.was_started("JUMP", &input));
+
+        frame(&mut input, &[Event::KeyReleased(KeyCode::Space)]);
+        assert!(map.is_active("JUMP", &input));
+        assert!(!map.was_stopped("JUMP", &input));
+
+        // Swapping one for the other within a frame neither starts nor stops the action
+        frame(&mut input, &[Event::KeyPressed(KeyCode::Space), Event::KeyReleased(KeyCode::W)]);
+        assert!(!map.was_started("JUMP", &input));
+        assert!(!map.was_stopped("JUMP", &input));
+
+        frame(&mut input, &[Event::KeyReleased(KeyCode::Space)]);
+        assert!(map.was_stopped("JUMP", &input));
+    }
+
+    #[test]
+    fn mouse_buttons() {
+        let map = map();
+        let mut input = Input::new();
+
+        frame(&mut input, &[Event::MousePressed(MouseButton::Left, 5, 5)]);
+        assert!(map.was_started("FIRE", &input));
+        assert!(!map.was_started("JUMP", &input));
+
+        frame(&mut input, &[Event::MouseReleased(MouseButton::Left, 5, 5)]);
+        assert!(map.was_stopped("FIRE", &input));
+    }
+
+    #[test]
+    fn rejects_malformed_bindings() {
+        assert!(ActionMap::from_str("JUMP").is_none());
+        assert!(ActionMap::from_str("JUMP Spacebar").is_none());
+        assert!(ActionMap::from_str("JUMP Space\nFIRE Space").is_none());
+        assert!(ActionMap::from_str("JUMP Space\nJUMP Space W").is_some());
+    }
+}
@@ -125,6 +125,21 @@ impl super::Collide<AABB> for AABB {
 
         return None;
     }
+
+
+    fn sweep(&self, other: &AABB, motion: Vector2) -> Option<(f64, Vector2)> {
+        let hull: super::ConvexHull = self.clone().into();
+        let other: super::ConvexHull = other.clone().into();
+
+        hull.sweep(&other, motion)
+    }
+
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        let hull: super::ConvexHull = self.clone().into();
+
+        hull.raycast(origin, direction)
+    }
 }
 
 
@@ -134,5 +149,19 @@ impl super::Collide<super::ConvexHull> for AABB {
 
         hull.overlap(other)
     }
+
+
+    fn sweep(&self, other: &super::ConvexHull, motion: Vector2) -> Option<(f64, Vector2)> {
+        let hull: super::ConvexHull = self.clone().into();
+
+        hull.sweep(other, motion)
+    }
+
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        let hull: super::ConvexHull = self.clone().into();
+
+        hull.raycast(origin, direction)
+    }
 }
 
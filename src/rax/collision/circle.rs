@@ -42,4 +42,31 @@ impl super::Collide<super::ConvexHull> for Circle {
             n => n,
         }
     }
+
+
+    // The same as the hull moving the other way into the circle
+    fn sweep(&self, other: &super::ConvexHull, motion: Vector2) -> Option<(f64, Vector2)> {
+        other.sweep(self, -motion).map(|(time, normal)| (time, -normal))
+    }
+
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        // Solve |offset + direction * time| = radius for the first time the circle is entered
+        let offset = origin - self.center;
+        let a = direction.dot(direction);
+        let b = 2.0 * offset.dot(direction);
+        let c = offset.dot(offset) - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+
+        if a <= 0.0 || c < 0.0 || discriminant < 0.0 {
+            return None;
+        }
+
+        let time = (-b - discriminant.sqrt()) / (2.0 * a);
+        if time < 0.0 || time > 1.0 {
+            return None;
+        }
+
+        Some((time, (offset + direction * time).norm()))
+    }
 }
\ No newline at end of file
@@ -114,6 +114,60 @@ impl ConvexHull {
             *point += amount;
         }
     }
+
+
+    /// Return the fraction of a ray travelled before it enters this hull, and the normal of the
+    /// edge it enters through
+    pub fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        use std::f64::INFINITY;
+
+        let center = self.average();
+
+        // The ray is inside while it is behind every edge at the same time
+        let mut enter = -INFINITY;
+        let mut exit = INFINITY;
+        let mut normal = None;
+
+        for i in 0..self.points.len() {
+            let start: Vector2 = self.points[i];
+            let end: Vector2 = self.points[(i + 1) % self.points.len()];
+
+            let edge = (end - start).norm();
+            let mut edge_normal = Vector2::from([edge.y, -edge.x]);
+            if edge_normal.dot(center - start) > 0.0 {
+                edge_normal = -edge_normal;
+            }
+
+            let distance = edge_normal.dot(start - origin);
+            let speed = edge_normal.dot(direction);
+
+            if speed.abs() < 1e-12 {
+                // Parallel to the edge, and in front of it
+                if distance < 0.0 {
+                    return None;
+                }
+            } else if speed < 0.0 {
+                let time = distance / speed;
+                if time > enter {
+                    enter = time;
+                    normal = Some(edge_normal);
+                }
+            } else {
+                let time = distance / speed;
+                if time < exit {
+                    exit = time;
+                }
+            }
+        }
+
+        let normal = if let Some(normal) = normal { normal } else { return None };
+
+        if enter < 0.0 || enter > 1.0 || enter > exit {
+            return None;
+        }
+
+        Some((enter, normal))
+    }
 }
 
 
@@ -187,6 +241,78 @@ impl super::Collide<ConvexHull> for ConvexHull {
             None
         }
     }
+
+
+    fn sweep(&self, other: &ConvexHull, motion: Vector2) -> Option<(f64, Vector2)> {
+        use std::f64::INFINITY;
+        use super::CONTACT_TOLERANCE;
+
+        let axes = self.axes.iter().chain(other.axes.iter());
+
+        // The shapes touch while they overlap on every axis at the same time
+        let mut enter = -INFINITY;
+        let mut exit = INFINITY;
+        let mut normal = None;
+
+        // Speed along the normal, which breaks ties between axes that start touching together
+        let mut enter_speed = INFINITY;
+
+        for axis in axes {
+            let (self_min, self_max) = super::projected_range(self.points.as_slice(), *axis);
+            let (other_min, other_max) = super::projected_range(other.points.as_slice(), *axis);
+
+            let speed = axis.dot(motion);
+
+            if speed.abs() < 1e-12 {
+                if other_max - self_min > CONTACT_TOLERANCE && self_max - other_min > CONTACT_TOLERANCE {
+                    continue;
+                } else {
+                    return None;
+                }
+            }
+
+            // Distance to cover before the shapes start and stop overlapping on this axis
+            let (gap, depth, axis_normal) = if speed > 0.0 {
+                (self_min - other_max, self_max - other_min, -*axis)
+            } else {
+                (other_min - self_max, other_max - self_min, *axis)
+            };
+
+            // Shapes that are just barely overlapping count as touching
+            let axis_enter = if gap > -CONTACT_TOLERANCE { gap.max(0.0) } else { gap } / speed.abs();
+            let axis_exit = depth / speed.abs();
+
+            // A corner that touches two edges at once, like the foot of a slope, is hit on the
+            // edge it approaches the least head on, like the smallest overlap would be resolved
+            let tie = (axis_enter - enter).abs() * motion.len() < CONTACT_TOLERANCE;
+
+            if (tie && speed.abs() < enter_speed) || (!tie && axis_enter > enter) {
+                enter = axis_enter;
+                enter_speed = speed.abs();
+                normal = Some(axis_normal);
+            }
+            if axis_exit < exit {
+                exit = axis_exit;
+            }
+        }
+
+        let normal = if let Some(normal) = normal { normal } else { return None };
+
+        if enter < 0.0 || enter > 1.0 || enter >= exit {
+            return None;
+        }
+
+        if self.ignored_normals.contains(&normal) || other.ignored_normals.contains(&(-normal)) {
+            return None;
+        }
+
+        Some((enter, normal))
+    }
+
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        ConvexHull::raycast(self, origin, direction)
+    }
 }
 
 
@@ -277,4 +403,63 @@ impl super::Collide<super::Circle> for ConvexHull {
 
         None
     }
+
+    fn sweep(&self, circle: &super::Circle, motion: Vector2) -> Option<(f64, Vector2)> {
+        use super::CONTACT_TOLERANCE;
+
+        if self.overlap(circle).is_some() {
+            return None;
+        }
+
+        // The circle hits the hull when its center hits the hull grown by the radius: the edges
+        // moved out along their normals, joined by circles around the corners
+        let mut first: Option<(f64, Vector2)> = None;
+        let mut hit = |time: f64, normal: Vector2| {
+            if first.map_or(true, |(best, _)| time < best) {
+                first = Some((time, normal));
+            }
+        };
+
+        for i in 0..self.points.len() {
+            let start: Vector2 = self.points[i];
+            let end: Vector2 = self.points[(i + 1) % self.points.len()];
+
+            let edge = (end - start).norm();
+            let normal = Vector2::from([edge.y, -edge.x]);
+
+            let distance = normal.dot(circle.center - start) - circle.radius;
+            let speed = normal.dot(motion);
+
+            if speed < 0.0 && distance > -CONTACT_TOLERANCE && !self.ignored_normals.contains(&normal) {
+                let time = distance.max(0.0) / -speed;
+                let along = edge.dot(circle.center + motion * time - start);
+
+                if time <= 1.0 && 0.0 <= along && along <= (end - start).len() {
+                    hit(time, normal);
+                }
+            }
+
+            // Solve |offset + motion * time| = radius for the first time the corner is touched
+            let offset = circle.center - start;
+            let a = motion.dot(motion);
+            let b = 2.0 * offset.dot(motion);
+            let c = offset.dot(offset) - circle.radius * circle.radius;
+            let discriminant = b * b - 4.0 * a * c;
+
+            if a > 0.0 && b < 0.0 && discriminant >= 0.0 {
+                let time = ((-b - discriminant.sqrt()) / (2.0 * a)).max(0.0);
+
+                if time <= 1.0 {
+                    hit(time, (offset + motion * time).norm());
+                }
+            }
+        }
+
+        first
+    }
+
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        ConvexHull::raycast(self, origin, direction)
+    }
 }
\ No newline at end of file
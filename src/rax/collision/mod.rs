@@ -13,9 +13,22 @@ pub use self::circle::Circle;
 mod rectangle;
 pub use self::rectangle::Rectangle;
 
+/// Shapes closer than this are considered to be touching, rather than overlapping
+pub const CONTACT_TOLERANCE: f64 = 1e-6;
+
+
 pub trait Collide<C>: Bounded {
     // Return the overlap depth and the minimal translation vector of self
     fn overlap(&self, other: &C) -> Option<(f64, Vector2)>;
+
+    // Return the fraction of a motion the other shape can make before it hits self, and the normal
+    // of self at the point of contact. Shapes that already overlap are not reported.
+    fn sweep(&self, other: &C, motion: Vector2) -> Option<(f64, Vector2)>;
+
+    // Return the fraction of a ray, from an origin to origin + direction, that is travelled before
+    // it enters self, and the normal of self where it enters. Rays that start inside are not
+    // reported.
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)>;
 }
 
 
@@ -0,0 +1,49 @@
+use super::{KeyCode, MouseButton};
+
+
+/// Something that happened to the window, or input it received
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The window was closed
+    Closed,
+
+    /// The window changed size, to a width and height
+    Resized(u64, u64),
+
+    /// The window gained or lost focus. Keys held when focus is lost are released first.
+    FocusGained,
+    FocusLost,
+
+    KeyPressed(KeyCode),
+    KeyReleased(KeyCode),
+
+    /// Shift, control, alt or logo was pressed or released
+    ModifiersChanged(Modifiers),
+
+    /// A character was typed, after the keyboard layout and modifiers are applied
+    Character(char),
+
+    /// A mouse button was pressed or released, at a position in the window
+    MousePressed(MouseButton, u64, u64),
+    MouseReleased(MouseButton, u64, u64),
+
+    /// The cursor moved to a position in the window
+    MouseMoved(u64, u64),
+
+    /// The mouse wheel was scrolled a number of lines sideways and upwards
+    MouseWheel(f64, f64),
+
+    /// The cursor entered or left the window
+    CursorEntered,
+    CursorLeft,
+}
+
+
+/// The modifier keys that are held down
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
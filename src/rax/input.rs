@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use trap::{Vector2, Vector2i};
+
+use super::{KeyCode, MouseButton};
+use super::event::{Event, Modifiers};
+use super::view::View;
+
+
+/// The state of the keyboard and mouse, updated by the window every frame
+#[derive(Clone)]
+pub struct Input {
+    keys_down: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+
+    modifiers: Modifiers,
+
+    // Position of the cursor in the window, in pixels
+    cursor_position: Vector2i,
+}
+
+
+impl Input {
+    /// Create an input state where nothing is held down
+    pub fn new() -> Input {
+        Input {
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+
+            modifiers: Modifiers::default(),
+
+            cursor_position: Vector2i::new(0, 0),
+        }
+    }
+
+
+    /// Forgets what was pressed and released during the last frame
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+    }
+
+
+    /// Updates the state with an event
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            Event::KeyPressed(key) => {
+                self.keys_down.insert(key);
+                self.keys_pressed.insert(key);
+            },
+            Event::KeyReleased(key) => {
+                self.keys_down.remove(&key);
+                self.keys_released.insert(key);
+            },
+
+            Event::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+
+            Event::MousePressed(button, x, y) => {
+                self.buttons_down.insert(button);
+                self.buttons_pressed.insert(button);
+                self.cursor_position = Vector2i::new(x as i64, y as i64);
+            },
+            Event::MouseReleased(button, x, y) => {
+                self.buttons_down.remove(&button);
+                self.buttons_released.insert(button);
+                self.cursor_position = Vector2i::new(x as i64, y as i64);
+            },
+
+            Event::MouseMoved(x, y) => self.cursor_position = Vector2i::new(x as i64, y as i64),
+
+            _ => ()
+        }
+    }
+
+
+    /// Returns true if a key is held down
+    pub fn is_down(&self, key: KeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// Returns true if a key was pressed since the last frame
+    pub fn was_pressed_this_frame(&self, key: KeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    /// Returns true if a key was released since the last frame
+    pub fn was_released_this_frame(&self, key: KeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    /// Returns every key that is held down
+    pub fn get_keys_down(&self) -> Vec<KeyCode> {
+        self.keys_down.iter().cloned().collect()
+    }
+
+
+    /// Returns true if a mouse button is held down
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    /// Returns every mouse button that is held down
+    pub fn get_mouse_down(&self) -> Vec<MouseButton> {
+        self.buttons_down.iter().cloned().collect()
+    }
+
+    /// Returns true if a mouse button was pressed since the last frame
+    pub fn was_mouse_pressed_this_frame(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    /// Returns true if a mouse button was released since the last frame
+    pub fn was_mouse_released_this_frame(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+
+    /// Returns the modifier keys that are held down
+    pub fn get_modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+
+    /// Returns the position of the cursor in the window, in pixels
+    pub fn get_cursor_position(&self) -> Vector2i {
+        self.cursor_position
+    }
+
+    /// Returns the point in the world under the cursor, as seen through a view
+    pub fn get_cursor_world(&self, view: &View) -> Vector2 {
+        view.screen_to_world(self.cursor_position)
+    }
+}
@@ -7,16 +7,30 @@ pub use self::window::{KeyCode, MouseButton};
 mod renderer;
 pub use self::renderer::Renderer;
 
+pub mod actions;
 pub mod collision;
+pub mod event;
+pub mod input;
+pub mod scene;
+pub mod view;
+
+use self::event::Event;
+use self::input::Input;
 
 
 pub trait Game {
-    /// Updates the game
-    fn update(&mut self, dt: f64);
+    /// Updates the game, once every frame, with the keys and mouse buttons held and pressed
+    fn update(&mut self, dt: f64, input: &Input);
+
+    /// Updates the game in steps of the fixed timestep, if the game was built with one, with the
+    /// input of the frame the steps are taken in
+    #[allow(unused_variables)]
+    fn fixed_update(&mut self, dt: f64, input: &Input) {}
 
 
-    /// Renders the game to the screen
-    fn render(&mut self, renderer: &mut Renderer);
+    /// Renders the game to the screen. Alpha is how far the game is between the last fixed update
+    /// and the next one, from 0 to 1, and always 1 without a fixed timestep.
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64);
 
 
     /// Returns true if the game is running, false otherwise
@@ -24,6 +38,11 @@ pub trait Game {
 
 
 
+    /// Called for every event, before the callback for that kind of event
+    #[allow(unused_variables)]
+    fn on_event(&mut self, event: Event) {}
+
+
     /// Called when the window is closed
     fn on_close(&mut self) {}
 
@@ -56,38 +75,35 @@ pub trait Game {
 
 
 impl<T: Game> WindowHandler for T {
-    fn closed(&mut self) {
-        self.on_close();
-    }
-
-    fn key_pressed(&mut self, key: KeyCode) {
-        self.on_key_press(key);
-    }
+    fn event(&mut self, event: Event) {
+        self.on_event(event);
 
-    fn key_released(&mut self, key: KeyCode) {
-        self.on_key_release(key);
-    }
+        match event {
+            Event::Closed => self.on_close(),
+            Event::Resized(width, height) => self.on_size_change(width, height),
 
-    fn mouse_pressed(&mut self, key: MouseButton, x: u64, y: u64) {
-        self.on_mouse_press(key, x, y);
-    }
+            Event::KeyPressed(key) => self.on_key_press(key),
+            Event::KeyReleased(key) => self.on_key_release(key),
 
-    fn mouse_released(&mut self, key: MouseButton, x: u64, y: u64) {
-        self.on_mouse_release(key, x, y);
-    }
-
-    fn mouse_moved(&mut self, x: u64, y: u64) {
-        self.on_mouse_move(x, y);
-    }
+            Event::MousePressed(button, x, y) => self.on_mouse_press(button, x, y),
+            Event::MouseReleased(button, x, y) => self.on_mouse_release(button, x, y),
+            Event::MouseMoved(x, y) => self.on_mouse_move(x, y),
 
-    fn size_changed(&mut self, width: u64, height: u64) {
-        self.on_size_change(width, height);
+            _ => ()
+        }
     }
 }
 
 
 pub struct GameBuilder {
-    window: WindowSettings
+    window: WindowSettings,
+
+    // Time between fixed updates, in seconds
+    fixed_timestep: Option<f64>,
+
+    // Longest time caught up on in a single frame, so that slow frames do not cause even more
+    // fixed updates the next frame
+    max_frame_time: f64,
 }
 
 impl GameBuilder {
@@ -100,7 +116,10 @@ impl GameBuilder {
                 fullscreen: false,
                 vsync: true,
                 samples: 4,
-            }
+            },
+
+            fixed_timestep: None,
+            max_frame_time: 0.25,
         }
     }
 
@@ -111,6 +130,8 @@ impl GameBuilder {
         let mut window = Window::new(self.window);
         let mut renderer = Renderer::new(window.get_display());
 
+        let mut accumulator = 0.0;
+
         let mut last = Instant::now();
         while window.is_open() && game.is_running() {
             let now = Instant::now();
@@ -118,10 +139,24 @@ impl GameBuilder {
             let delta_time = duration.as_secs() as f64 + 1e-9 * duration.subsec_nanos() as f64;
             window.handle_events(&mut game);
 
-            game.update(delta_time);
+            game.update(delta_time, window.get_input());
+
+            // After a stall the game slows down instead of skipping ahead
+            let alpha = if let Some(timestep) = self.fixed_timestep {
+                accumulator = (accumulator + delta_time).min(self.max_frame_time);
+
+                while accumulator >= timestep {
+                    game.fixed_update(timestep, window.get_input());
+                    accumulator -= timestep;
+                }
+
+                accumulator / timestep
+            } else {
+                1.0
+            };
 
             renderer.begin();
-            game.render(&mut renderer);
+            game.render(&mut renderer, alpha);
             renderer.end();
 
             last = now;
@@ -182,4 +217,22 @@ impl GameBuilder {
             .. self
         }
     }
+
+
+    /// Calls Game::fixed_update a number of times per second
+    pub fn with_fixed_timestep(self, hz: f64) -> Self {
+        GameBuilder {
+            fixed_timestep: Some(1.0 / hz),
+            .. self
+        }
+    }
+
+
+    /// Set the longest time, in seconds, that fixed updates catch up on in a single frame
+    pub fn with_max_frame_time(self, max_frame_time: f64) -> Self {
+        GameBuilder {
+            max_frame_time,
+            .. self
+        }
+    }
 }
\ No newline at end of file
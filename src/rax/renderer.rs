@@ -2,6 +2,8 @@ use glium;
 use glium::{Display, Surface, Frame};
 use trap::{Vector2, Vector2i};
 
+use super::view::View;
+
 pub struct Renderer {
     display: Display,
     program: glium::Program,
@@ -12,8 +14,17 @@ pub struct Renderer {
     center: Vector2,
     size: Vector2i,
 
+    // Number of pixels a unit covers
+    zoom: f64,
+
+    // Clockwise rotation of the camera, in radians
+    rotation: f64,
+
     view: (f64, f64, f64, f64),
 
+    // Edges of the area that can be seen, larger than the view when rotated
+    visible: (f64, f64, f64, f64),
+
     viewport: glium::Rect,
 
     pub color: [f64; 4],
@@ -44,7 +55,11 @@ impl Renderer {
             center: Vector2::new(0.0, 0.0),
             size: Vector2i::new(2, 2),
 
+            zoom: 1.0,
+            rotation: 0.0,
+
             view: (-1.0, 1.0, 1.0, -1.0),
+            visible: (-1.0, 1.0, 1.0, -1.0),
             viewport: glium::Rect{
                 left: 0,
                 bottom: 0,
@@ -61,12 +76,10 @@ impl Renderer {
     }
 
     fn update_view(&mut self) {
-        self.view = (
-            self.center.x - self.viewport.width as f64 / 2.0,
-            self.center.x + self.viewport.width as f64 / 2.0,
-            self.center.y - self.viewport.height as f64 / 2.0,
-            self.center.y + self.viewport.height as f64 / 2.0
-        );
+        let view = self.get_view();
+
+        self.view = view.get_edges();
+        self.visible = view.get_visible_edges();
     }
 
 
@@ -82,6 +95,56 @@ impl Renderer {
     }
 
 
+    /// Set how many pixels a unit covers, smaller values show more of the world
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.zoom = zoom;
+        self.update_view();
+    }
+
+    /// Get the current zoom of the camera
+    pub fn get_zoom(&self) -> f64 {
+        self.zoom
+    }
+
+
+    /// Set the clockwise rotation of the camera, in radians
+    pub fn set_rotation(&mut self, rotation: f64) {
+        self.rotation = rotation;
+        self.update_view();
+    }
+
+    /// Get the current rotation of the camera
+    pub fn get_rotation(&self) -> f64 {
+        self.rotation
+    }
+
+
+    /// Get the camera and viewport currently rendered with, it can be kept to convert mouse
+    /// positions after rendering
+    pub fn get_view(&self) -> View {
+        let top = self.size.y as f64 - self.viewport.bottom as f64 - self.viewport.height as f64;
+
+        View {
+            center: self.center,
+            zoom: self.zoom,
+            rotation: self.rotation,
+
+            viewport_position: Vector2::new(self.viewport.left as f64, top),
+            viewport_size: Vector2::new(self.viewport.width as f64, self.viewport.height as f64),
+        }
+    }
+
+    /// Returns the point in the world under a pixel in the window
+    pub fn screen_to_world(&self, screen: Vector2i) -> Vector2 {
+        self.get_view().screen_to_world(screen)
+    }
+
+    /// Returns the pixel in the window a point in the world is drawn at
+    pub fn world_to_screen(&self, world: Vector2) -> Vector2 {
+        self.get_view().world_to_screen(world)
+    }
+
+
     /// Sets the viewport to use in the next render
     pub fn set_viewport(&mut self, left: u32, right: u32, top: u32, bottom: u32) {
         self.flush();
@@ -109,6 +172,8 @@ impl Renderer {
 
         self.center.x = self.size.x as f64 / 2.0;
         self.center.y = self.size.y as f64 / 2.0;
+        self.zoom = 1.0;
+        self.rotation = 0.0;
 
         self.update_view();
     }
@@ -146,7 +211,9 @@ impl Renderer {
                 left: left as f32,
                 right: right as f32,
                 top: top as f32,
-                bottom: bottom as f32
+                bottom: bottom as f32,
+                center: [self.center.x as f32, self.center.y as f32],
+                rotation: self.rotation as f32
             );
 
             let parameters = glium::DrawParameters {
@@ -363,8 +430,8 @@ impl Renderer {
 
     /// Determines if a rectangle is in view
     fn rectangle_visible(&self, left: f64, right: f64, top: f64, bottom: f64) -> bool {
-        left < self.view.1 && self.view.0 < right &&
-            top < self.view.3 && self.view.2 < bottom
+        left < self.visible.1 && self.visible.0 < right &&
+            top < self.visible.3 && self.visible.2 < bottom
     }
 }
 
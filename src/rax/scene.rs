@@ -0,0 +1,285 @@
+use std::any::Any;
+
+use trap::Vector2;
+
+use super::{Game, Renderer, KeyCode, MouseButton};
+use super::event::Event;
+use super::input::Input;
+
+
+/// A part of a game, such as a level or a menu, that is kept on a scene stack
+pub trait Scene: Game {
+    /// Returns true if the scene is drawn on top of the scene below it, which is then rendered
+    /// but not updated
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Returns a change the scene wants to make to the stack, checked after every callback
+    fn take_transition(&mut self) -> Option<Transition> {
+        None
+    }
+
+    /// Returns the scene as Any, so that the game can find out which of its scenes it is
+    fn as_any(&self) -> &Any;
+}
+
+
+/// A change to a scene stack
+pub enum Transition {
+    /// Puts a scene on top of the stack
+    Push(Box<Scene>),
+
+    /// Closes the scene on top of the stack
+    Pop,
+
+    /// Closes the scene on top of the stack and puts another one in its place
+    Replace(Box<Scene>),
+
+    /// Fades the screen to black over a number of seconds, makes a change, and fades back in
+    Fade(f64, Box<Transition>),
+}
+
+
+/// Scenes on top of each other, where events go to the scene on top. Scenes that stop running are
+/// closed, and the stack stops running once it is empty.
+pub struct SceneStack {
+    scenes: Vec<Box<Scene>>,
+
+    fade: Option<Fade>,
+
+    window_size: [u64; 2],
+}
+
+
+/// A transition that is fading out the screen, or back in after its change was made
+struct Fade {
+    duration: f64,
+    time: f64,
+
+    // The change to make once the screen is black, None once it has been made
+    change: Option<Transition>,
+}
+
+
+impl SceneStack {
+    /// Create an empty stack
+    pub fn new() -> SceneStack {
+        SceneStack {
+            scenes: Vec::new(),
+            fade: None,
+            window_size: [0, 0],
+        }
+    }
+
+
+    /// Puts a scene on top of the stack
+    pub fn push(&mut self, mut scene: Box<Scene>) {
+        scene.on_size_change(self.window_size[0], self.window_size[1]);
+        self.scenes.push(scene);
+    }
+
+
+    /// Closes the scene on top of the stack, and returns it
+    pub fn pop(&mut self) -> Option<Box<Scene>> {
+        let mut scene = self.scenes.pop();
+        if let Some(ref mut scene) = scene {
+            scene.on_close();
+        }
+        scene
+    }
+
+
+    /// Closes the scene on top of the stack and puts another one in its place
+    pub fn replace(&mut self, scene: Box<Scene>) {
+        self.pop();
+        self.push(scene);
+    }
+
+
+    /// Makes a change to the stack, or starts fading towards it
+    pub fn transition(&mut self, transition: Transition) {
+        match transition {
+            Transition::Push(scene) => self.push(scene),
+            Transition::Pop => { self.pop(); },
+            Transition::Replace(scene) => self.replace(scene),
+
+            Transition::Fade(duration, change) => {
+                // A fade that has not made its change yet is cut short
+                if let Some(change) = self.fade.take().and_then(|fade| fade.change) {
+                    self.transition(change);
+                }
+
+                self.fade = Some(Fade {
+                    duration,
+                    time: 0.0,
+                    change: Some(*change),
+                });
+            }
+        }
+    }
+
+
+    /// Returns the number of scenes on the stack
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+
+    /// Returns true if there are no scenes on the stack
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+
+    /// Returns the scene below the overlays on top of the stack, or the top scene if it is not an
+    /// overlay
+    pub fn get_base(&self) -> Option<&Scene> {
+        self.scenes.get(self.base_index()).map(|scene| &**scene)
+    }
+
+
+    /// Returns the position of the first scene from the top that is not an overlay
+    fn base_index(&self) -> usize {
+        self.scenes.iter().rposition(|scene| !scene.is_overlay()).unwrap_or(0)
+    }
+
+
+    /// Makes the changes the scene on top wants, and closes it if it stopped running
+    fn poll_top(&mut self) {
+        let transition = self.scenes.last_mut().and_then(|scene| scene.take_transition());
+        if let Some(transition) = transition {
+            self.transition(transition);
+        }
+
+        if self.scenes.last().map_or(false, |scene| !scene.is_running()) {
+            self.pop();
+        }
+    }
+
+
+    /// Advances the fade, and makes its change once the screen is black
+    fn update_fade(&mut self, dt: f64) {
+        let mut change = None;
+
+        if let Some(ref mut fade) = self.fade {
+            fade.time += dt;
+
+            if fade.time >= fade.duration && fade.change.is_some() {
+                fade.time = 0.0;
+                change = fade.change.take();
+            }
+        }
+
+        if let Some(change) = change {
+            self.transition(change);
+        }
+
+        let done = self.fade.as_ref().map_or(false, |fade| fade.change.is_none() && fade.time >= fade.duration);
+        if done {
+            self.fade = None;
+        }
+    }
+
+
+    /// Returns how dark the screen is, from 0 to 1
+    fn get_fade_opacity(&self) -> f64 {
+        if let Some(ref fade) = self.fade {
+            let progress = if fade.duration > 0.0 { (fade.time / fade.duration).min(1.0) } else { 1.0 };
+
+            if fade.change.is_some() { progress } else { 1.0 - progress }
+        } else {
+            0.0
+        }
+    }
+
+
+    /// Runs a callback on the scene on top, and makes the changes it wants afterwards
+    fn with_top<F: FnOnce(&mut Box<Scene>)>(&mut self, callback: F) {
+        if let Some(scene) = self.scenes.last_mut() {
+            callback(scene);
+        }
+
+        self.poll_top();
+    }
+}
+
+
+impl Game for SceneStack {
+    fn update(&mut self, dt: f64, input: &Input) {
+        self.update_fade(dt);
+        self.with_top(|scene| scene.update(dt, input));
+    }
+
+    fn fixed_update(&mut self, dt: f64, input: &Input) {
+        self.with_top(|scene| scene.fixed_update(dt, input));
+    }
+
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64) {
+        // Overlays are drawn on top of every scene down to the first one that is not an overlay
+        let bottom = self.base_index();
+
+        for scene in self.scenes[bottom..].iter_mut() {
+            scene.render(renderer, alpha);
+        }
+
+        let opacity = self.get_fade_opacity();
+        if opacity > 0.0 {
+            let (width, height) = (self.window_size[0], self.window_size[1]);
+
+            renderer.set_viewport(0, width as u32, 0, height as u32);
+            renderer.set_center(Vector2::new(width as f64 / 2.0, height as f64 / 2.0));
+            renderer.set_zoom(1.0);
+            renderer.set_rotation(0.0);
+
+            renderer.color = [0.0, 0.0, 0.0, opacity];
+            renderer.fill_rectangle(0.0, width as f64, 0.0, height as f64);
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        !self.scenes.is_empty()
+    }
+
+
+    fn on_event(&mut self, event: Event) {
+        self.with_top(|scene| scene.on_event(event));
+    }
+
+
+    fn on_close(&mut self) {
+        while self.pop().is_some() {}
+    }
+
+
+    fn on_key_press(&mut self, key: KeyCode) {
+        self.with_top(|scene| scene.on_key_press(key));
+    }
+
+    fn on_key_release(&mut self, key: KeyCode) {
+        self.with_top(|scene| scene.on_key_release(key));
+    }
+
+
+    fn on_mouse_press(&mut self, button: MouseButton, x: u64, y: u64) {
+        self.with_top(|scene| scene.on_mouse_press(button, x, y));
+    }
+
+    fn on_mouse_release(&mut self, button: MouseButton, x: u64, y: u64) {
+        self.with_top(|scene| scene.on_mouse_release(button, x, y));
+    }
+
+    fn on_mouse_move(&mut self, x: u64, y: u64) {
+        self.with_top(|scene| scene.on_mouse_move(x, y));
+    }
+
+
+    fn on_size_change(&mut self, width: u64, height: u64) {
+        self.window_size = [width, height];
+
+        // Scenes below the top are drawn too, so they all need to know
+        for scene in self.scenes.iter_mut() {
+            scene.on_size_change(width, height);
+        }
+    }
+}
@@ -7,15 +7,24 @@ uniform float right = 1.0;
 uniform float top = 1.0;
 uniform float bottom = -1.0;
 
+uniform vec2 center = vec2(0.0);
+uniform float rotation = 0.0;
+
 out FragData {
     vec2 position;
     vec4 color;
 } frag;
 
 void main() {
+    // Turn the world the opposite way of the camera, around the center of the view
+    float c = cos(rotation);
+    float s = sin(rotation);
+    vec2 offset = position - center;
+    vec2 rotated = center + vec2(offset.x * c + offset.y * s, -offset.x * s + offset.y * c);
+
     vec2 lb = vec2(left, bottom);
     vec2 rt = vec2(right, top);
-    vec2 translated = (position - lb) * 2.0 / (rt - lb) - vec2(1.0);
+    vec2 translated = (rotated - lb) * 2.0 / (rt - lb) - vec2(1.0);
 	gl_Position = vec4(translated, 0.0, 1.0);
 
 	frag.position = position;
@@ -0,0 +1,161 @@
+use trap::{Vector2, Vector2i};
+
+
+/// Where the camera looks and the part of the window it is drawn to, used to convert between
+/// points on the screen and in the world
+#[derive(Copy, Clone)]
+pub struct View {
+    /// The point in the world in the middle of the viewport
+    pub center: Vector2,
+
+    /// Number of pixels a unit covers
+    pub zoom: f64,
+
+    /// Clockwise rotation of the camera, in radians. The world appears rotated the other way.
+    pub rotation: f64,
+
+    /// The top left corner and size of the viewport in the window, in pixels
+    pub viewport_position: Vector2,
+    pub viewport_size: Vector2,
+}
+
+
+impl View {
+    /// Create a view looking at a point with a viewport covering a window
+    pub fn new(center: Vector2, window_size: Vector2) -> View {
+        View {
+            center,
+            zoom: 1.0,
+            rotation: 0.0,
+
+            viewport_position: Vector2::new(0.0, 0.0),
+            viewport_size: window_size,
+        }
+    }
+
+
+    /// Returns the point in the world under a pixel in the window
+    pub fn screen_to_world(&self, screen: Vector2i) -> Vector2 {
+        let offset = (Vector2::from(screen) - self.viewport_position - self.viewport_size / 2.0) / self.zoom;
+        self.center + rotate(offset, self.rotation)
+    }
+
+
+    /// Returns the pixel in the window a point in the world is drawn at
+    pub fn world_to_screen(&self, world: Vector2) -> Vector2 {
+        let offset = rotate(world - self.center, -self.rotation) * self.zoom;
+        self.viewport_position + self.viewport_size / 2.0 + offset
+    }
+
+
+    /// Returns the left, right, top and bottom edges of the rectangle in the world the camera
+    /// looks at, before it is rotated
+    pub fn get_edges(&self) -> (f64, f64, f64, f64) {
+        let half = self.viewport_size / 2.0 / self.zoom;
+
+        (
+            self.center.x - half.x,
+            self.center.x + half.x,
+            self.center.y - half.y,
+            self.center.y + half.y,
+        )
+    }
+
+
+    /// Returns the left, right, top and bottom edges of a rectangle in the world around everything
+    /// that can be seen
+    pub fn get_visible_edges(&self) -> (f64, f64, f64, f64) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let half = self.viewport_size / 2.0 / self.zoom;
+
+        let half_width = half.x * cos.abs() + half.y * sin.abs();
+        let half_height = half.x * sin.abs() + half.y * cos.abs();
+
+        (
+            self.center.x - half_width,
+            self.center.x + half_width,
+            self.center.y - half_height,
+            self.center.y + half_height,
+        )
+    }
+}
+
+
+/// Rotates a vector clockwise, with the y-axis pointing down, by an angle in radians
+fn rotate(v: Vector2, angle: f64) -> Vector2 {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f64::consts::PI;
+
+    fn assert_near(a: Vector2, b: Vector2) {
+        assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9, "({}, {}) != ({}, {})", a.x, a.y, b.x, b.y);
+    }
+
+    /// A 200x100 viewport at (10, 20) in the window
+    fn view(zoom: f64, rotation: f64) -> View {
+        View {
+            center: Vector2::new(500.0, -300.0),
+            zoom,
+            rotation,
+
+            viewport_position: Vector2::new(10.0, 20.0),
+            viewport_size: Vector2::new(200.0, 100.0),
+        }
+    }
+
+    #[test]
+    fn middle_of_the_viewport_is_the_center() {
+        for &(zoom, rotation) in [(1.0, 0.0), (3.0, 0.0), (0.5, 1.0)].iter() {
+            let view = view(zoom, rotation);
+
+            assert_near(view.screen_to_world(Vector2i::new(110, 70)), view.center);
+            assert_near(view.world_to_screen(view.center), Vector2::new(110.0, 70.0));
+        }
+    }
+
+    #[test]
+    fn zoom_scales_distances() {
+        let view = view(2.0, 0.0);
+
+        assert_near(view.screen_to_world(Vector2i::new(130, 60)), Vector2::new(510.0, -305.0));
+        assert_near(view.world_to_screen(Vector2::new(510.0, -305.0)), Vector2::new(130.0, 60.0));
+    }
+
+    #[test]
+    fn rotation_turns_the_camera_clockwise() {
+        let view = view(2.0, PI / 2.0);
+
+        // Right on the screen is down in the world, and down on the screen is left in the world
+        assert_near(view.screen_to_world(Vector2i::new(130, 70)), Vector2::new(500.0, -290.0));
+        assert_near(view.screen_to_world(Vector2i::new(110, 90)), Vector2::new(490.0, -300.0));
+        assert_near(view.world_to_screen(Vector2::new(500.0, -290.0)), Vector2::new(130.0, 70.0));
+    }
+
+    #[test]
+    fn conversions_are_inverse() {
+        let view = view(1.75, 0.6);
+
+        for &(x, y) in [(0, 0), (10, 20), (210, 120), (-40, 333)].iter() {
+            let screen = Vector2i::new(x, y);
+            assert_near(view.world_to_screen(view.screen_to_world(screen)), Vector2::from(screen));
+        }
+    }
+
+    #[test]
+    fn visible_edges_cover_the_rotated_viewport() {
+        let (left, right, top, bottom) = view(2.0, 0.0).get_visible_edges();
+        assert_near(Vector2::new(left, top), Vector2::new(450.0, -325.0));
+        assert_near(Vector2::new(right, bottom), Vector2::new(550.0, -275.0));
+
+        let (left, right, top, bottom) = view(2.0, PI / 2.0).get_visible_edges();
+        assert_near(Vector2::new(left, top), Vector2::new(475.0, -350.0));
+        assert_near(Vector2::new(right, bottom), Vector2::new(525.0, -250.0));
+    }
+}
@@ -1,19 +1,24 @@
-use std::collections::HashSet;
-
 use glium;
 use glium::glutin;
 
 pub use glium::glutin::VirtualKeyCode as KeyCode;
 pub use glium::glutin::MouseButton;
 
+use super::event::{Event, Modifiers};
+use super::input::Input;
+
+
+/// How many pixels of scrolling, on touchpads, count as one line of the mouse wheel
+const PIXELS_PER_LINE: f64 = 20.0;
+
 pub struct Window {
     events_loop: glutin::EventsLoop,
     display: glium::Display,
 
     open: bool,
 
-    pressed_keys: HashSet<KeyCode>,
-    cursor_position: (u64, u64),
+    // Keys and mouse buttons held down, and where the cursor is
+    input: Input,
 }
 
 
@@ -49,8 +54,7 @@ impl Window {
 
             open: true,
 
-            pressed_keys: HashSet::new(),
-            cursor_position: (0, 0),
+            input: Input::new(),
         }
     }
 
@@ -61,55 +65,80 @@ impl Window {
     }
 
 
-    /// Poll all events and pass them to a handler
+    /// Returns the state of the keyboard and mouse
+    pub fn get_input(&self) -> &Input {
+        &self.input
+    }
+
+
+    /// Poll all events, update the input with them and pass them to a handler
     pub fn handle_events<H: WindowHandler>(&mut self, handler: &mut H) {
+        let mut events = Vec::new();
+
         let open = &mut self.open;
-        let pressed_keys = &mut self.pressed_keys;
-        let cursor_position = &mut self.cursor_position;
+        let cursor_position = self.input.get_cursor_position();
+        let (mut cursor_x, mut cursor_y) = (cursor_position.x as u64, cursor_position.y as u64);
 
         self.events_loop.poll_events(|e| {
-            use glium::glutin::{Event, WindowEvent, ElementState};
+            use glium::glutin::{WindowEvent, ElementState, MouseScrollDelta};
             match e {
-                Event::WindowEvent { event, .. } => {
+                glutin::Event::WindowEvent { event, .. } => {
                     match event {
                         WindowEvent::Closed => {
                             *open = false;
-                            handler.closed();
+                            events.push(Event::Closed);
                         }
 
                         WindowEvent::Resized(w, h) => {
-                            handler.size_changed(w as u64, h as u64);
+                            events.push(Event::Resized(w as u64, h as u64));
                         }
 
+                        WindowEvent::Focused(true) => events.push(Event::FocusGained),
+                        WindowEvent::Focused(false) => events.push(Event::FocusLost),
+
                         WindowEvent::KeyboardInput { input, .. } => {
+                            let state = input.modifiers;
+                            events.push(Event::ModifiersChanged(Modifiers {
+                                shift: state.shift,
+                                ctrl: state.ctrl,
+                                alt: state.alt,
+                                logo: state.logo,
+                            }));
+
                             if let Some(key) = input.virtual_keycode {
                                 match input.state {
-                                    ElementState::Pressed => {
-                                        if pressed_keys.insert(key) {
-                                            handler.key_pressed(key)
-                                        }
-                                    }
-                                    ElementState::Released => {
-                                        pressed_keys.remove(&key);
-                                        handler.key_released(key)
-                                    }
+                                    ElementState::Pressed => events.push(Event::KeyPressed(key)),
+                                    ElementState::Released => events.push(Event::KeyReleased(key)),
                                 }
                             }
                         }
 
+                        WindowEvent::ReceivedCharacter(character) => {
+                            events.push(Event::Character(character));
+                        }
+
                         WindowEvent::CursorMoved { position, .. } => {
-                            *cursor_position = (position.0.round() as u64, position.1.round() as u64);
-                            handler.mouse_moved(cursor_position.0, cursor_position.1);
+                            cursor_x = position.0.round() as u64;
+                            cursor_y = position.1.round() as u64;
+                            events.push(Event::MouseMoved(cursor_x, cursor_y));
+                        }
+
+                        WindowEvent::CursorEntered { .. } => events.push(Event::CursorEntered),
+                        WindowEvent::CursorLeft { .. } => events.push(Event::CursorLeft),
+
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            let (x, y) = match delta {
+                                MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+                                MouseScrollDelta::PixelDelta(x, y) => (x as f64 / PIXELS_PER_LINE, y as f64 / PIXELS_PER_LINE),
+                            };
+
+                            events.push(Event::MouseWheel(x, y));
                         }
 
                         WindowEvent::MouseInput { button, state, .. } => {
                             match state {
-                                ElementState::Pressed => {
-                                    handler.mouse_pressed(button, cursor_position.0, cursor_position.1)
-                                }
-                                ElementState::Released => {
-                                    handler.mouse_released(button, cursor_position.0, cursor_position.1)
-                                }
+                                ElementState::Pressed => events.push(Event::MousePressed(button, cursor_x, cursor_y)),
+                                ElementState::Released => events.push(Event::MouseReleased(button, cursor_x, cursor_y)),
                             }
                         }
 
@@ -120,6 +149,46 @@ impl Window {
                 _ => ()
             }
         });
+
+        self.input.begin_frame();
+
+        for event in events {
+            match event {
+                // Held keys repeat, but are only reported once
+                Event::KeyPressed(key) if self.input.is_down(key) => continue,
+                Event::KeyReleased(key) if !self.input.is_down(key) => continue,
+
+                Event::ModifiersChanged(modifiers) if modifiers == self.input.get_modifiers() => continue,
+
+                // Keys and buttons released while the window is not focused are never reported, so
+                // they are released here instead
+                Event::FocusLost => {
+                    for key in self.input.get_keys_down() {
+                        self.dispatch(Event::KeyReleased(key), handler);
+                    }
+
+                    let cursor = self.input.get_cursor_position();
+                    for button in self.input.get_mouse_down() {
+                        self.dispatch(Event::MouseReleased(button, cursor.x as u64, cursor.y as u64), handler);
+                    }
+
+                    if self.input.get_modifiers() != Modifiers::default() {
+                        self.dispatch(Event::ModifiersChanged(Modifiers::default()), handler);
+                    }
+                }
+
+                _ => ()
+            }
+
+            self.dispatch(event, handler);
+        }
+    }
+
+
+    /// Updates the input with an event and passes it to a handler
+    fn dispatch<H: WindowHandler>(&mut self, event: Event, handler: &mut H) {
+        self.input.handle(event);
+        handler.event(event);
     }
 
 
@@ -144,24 +213,6 @@ pub struct WindowSettings {
 
 
 pub trait WindowHandler {
-    /// Called when the window is closed
-    fn closed(&mut self);
-
-    /// Called when a key is pressed
-    fn key_pressed(&mut self, key: KeyCode);
-
-    /// Called when a key is released
-    fn key_released(&mut self, key: KeyCode);
-
-    /// Called when a mouse button is pressed
-    fn mouse_pressed(&mut self, key: MouseButton, x: u64, y: u64);
-
-    /// Called when a mouse button is released
-    fn mouse_released(&mut self, key: MouseButton, x: u64, y: u64);
-
-    /// Called when the cursor has moved
-    fn mouse_moved(&mut self, x: u64, y: u64);
-
-    /// Called when the window changes size
-    fn size_changed(&mut self, width: u64, height: u64);
+    /// Called for every event the window receives
+    fn event(&mut self, event: Event);
 }
@@ -0,0 +1,319 @@
+use std;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use player::PlayerCommand;
+use simulation::TICK_RATE;
+
+
+/// Version of the game that recorded a replay
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The longest replay that can be recorded or loaded, an hour of play
+pub const MAX_TICKS: u64 = 60 * 60 * TICK_RATE;
+
+
+/// The commands submitted during every tick of a level
+#[derive(Clone)]
+pub struct Replay {
+    version: String,
+
+    level_path: String,
+    level_hash: u64,
+
+    // The movement parameters the replay was recorded with, None if the file did not say
+    movement_hash: Option<u64>,
+
+    ticks: Vec<Vec<PlayerCommand>>,
+}
+
+
+impl Replay {
+    /// Start a new recording of a level, identified by its path and hash, played with movement
+    /// parameters that have a hash
+    pub fn new(level_path: &str, level_hash: u64, movement_hash: u64) -> Replay {
+        Replay {
+            version: VERSION.to_owned(),
+
+            level_path: level_path.to_owned(),
+            level_hash,
+            movement_hash: Some(movement_hash),
+
+            ticks: Vec::new(),
+        }
+    }
+
+
+    /// Open a replay from disk
+    pub fn open<P: AsRef<Path>>(path: P) -> Option<Replay> {
+        if let Ok(mut file) = File::open(path) {
+            use std::io::Read;
+
+            let mut string = String::new();
+            if file.read_to_string(&mut string).is_err() {
+                return None;
+            }
+
+            Replay::from_str(&string)
+        } else {
+            None
+        }
+    }
+
+
+    /// Save a replay to disk
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+        file.write_all(self.to_string().as_bytes())
+    }
+
+
+    /// Parses a string describing a replay
+    fn from_str(text: &str) -> Option<Replay> {
+        let mut replay = Replay {
+            version: String::new(),
+            level_path: String::new(),
+            level_hash: 0,
+            movement_hash: None,
+            ticks: Vec::new(),
+        };
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+
+            if let Some(word) = words.next() {
+                match word {
+                    // The version of the game that recorded the replay
+                    "VERSION" => {
+                        replay.version = words.collect::<Vec<_>>().join(" ");
+                    }
+
+
+                    // The level that was played
+                    "LEVEL" => {
+                        replay.level_path = line.trim()["LEVEL".len()..].trim().to_owned();
+                    }
+
+
+                    "LEVEL_HASH" => {
+                        if let Some(hash) = words.next().and_then(|h| u64::from_str_radix(h, 16).ok()) {
+                            replay.level_hash = hash;
+                        } else {
+                            println!("LEVEL_HASH: invalid hash!");
+                            return None;
+                        }
+                    }
+
+
+                    "MOVEMENT_HASH" => {
+                        if let Some(hash) = words.next().and_then(|h| u64::from_str_radix(h, 16).ok()) {
+                            replay.movement_hash = Some(hash);
+                        } else {
+                            println!("MOVEMENT_HASH: invalid hash!");
+                            return None;
+                        }
+                    }
+
+
+                    // Commands submitted for a number of ticks in a row
+                    "INPUT" => {
+                        let count: u64 = if let Some(count) = words.next().and_then(|c| c.parse().ok()) {
+                            count
+                        } else {
+                            println!("INPUT: invalid tick count!");
+                            return None;
+                        };
+
+                        if count > MAX_TICKS - replay.len() {
+                            println!("INPUT: replay is longer than {} ticks!", MAX_TICKS);
+                            return None;
+                        }
+
+                        let mut commands = Vec::new();
+                        for name in words {
+                            if name == "-" {
+                                continue;
+                            }
+
+                            if let Some(command) = PlayerCommand::from_name(name) {
+                                commands.push(command);
+                            } else {
+                                println!("INPUT: unknown command '{}'", name);
+                                return None;
+                            }
+                        }
+
+                        for _ in 0..count {
+                            replay.ticks.push(commands.clone());
+                        }
+                    }
+
+                    word => {
+                        println!("Failed to load replay: invalid command '{}'", word);
+                        return None;
+                    }
+                }
+            }
+        }
+
+        Some(replay)
+    }
+
+
+    /// Adds the commands submitted during the next tick, ticks after the longest possible replay
+    /// are left out
+    pub fn record(&mut self, commands: &[PlayerCommand]) {
+        if self.len() >= MAX_TICKS {
+            return;
+        }
+
+        let mut commands = commands.to_vec();
+        commands.sort();
+        commands.dedup();
+
+        self.ticks.push(commands);
+    }
+
+
+    /// Returns the commands submitted during a tick, or None after the end of the replay
+    pub fn get_commands(&self, tick: u64) -> Option<&[PlayerCommand]> {
+        self.ticks.get(tick as usize).map(|commands| commands.as_slice())
+    }
+
+
+    /// Returns the number of recorded ticks
+    pub fn len(&self) -> u64 {
+        self.ticks.len() as u64
+    }
+
+
+    pub fn get_version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn get_level_path(&self) -> &str {
+        &self.level_path
+    }
+
+
+    pub fn get_level_hash(&self) -> u64 {
+        self.level_hash
+    }
+
+    pub fn get_movement_hash(&self) -> Option<u64> {
+        self.movement_hash
+    }
+}
+
+
+impl fmt::Display for Replay {
+    // Written in the same format as the replay file, consecutive ticks with the same commands
+    // are written as a single line
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VERSION {}\nLEVEL {}\nLEVEL_HASH {:016x}", self.version, self.level_path, self.level_hash)?;
+
+        if let Some(movement_hash) = self.movement_hash {
+            write!(f, "\nMOVEMENT_HASH {:016x}", movement_hash)?;
+        }
+
+        let mut index = 0;
+        while index < self.ticks.len() {
+            let commands = &self.ticks[index];
+
+            let mut count = 1;
+            while index + count < self.ticks.len() && self.ticks[index + count] == *commands {
+                count += 1;
+            }
+
+            write!(f, "\nINPUT {}", count)?;
+            if commands.is_empty() {
+                write!(f, " -")?;
+            }
+            for command in commands {
+                write!(f, " {}", command)?;
+            }
+
+            index += count;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut replay = Replay::new("levels/test.lvl", 0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210);
+        replay.record(&[PlayerCommand::MoveRight]);
+        replay.record(&[PlayerCommand::MoveRight]);
+        replay.record(&[]);
+        replay.record(&[PlayerCommand::Jump, PlayerCommand::MoveLeft, PlayerCommand::Jump]);
+
+        let loaded = Replay::from_str(&replay.to_string()).unwrap();
+
+        assert_eq!(loaded.get_version(), VERSION);
+        assert_eq!(loaded.get_level_path(), "levels/test.lvl");
+        assert_eq!(loaded.get_level_hash(), 0x0123_4567_89ab_cdef);
+        assert_eq!(loaded.get_movement_hash(), Some(0xfedc_ba98_7654_3210));
+
+        assert_eq!(loaded.len(), 4);
+        for tick in 0..5 {
+            assert!(loaded.get_commands(tick) == replay.get_commands(tick));
+        }
+    }
+
+    #[test]
+    fn repeated_ticks_share_a_line() {
+        let mut replay = Replay::new("test.lvl", 0, 0);
+        for _ in 0..100 {
+            replay.record(&[PlayerCommand::MoveLeft]);
+        }
+
+        let text = replay.to_string();
+        assert!(text.ends_with("\nINPUT 100 MoveLeft"));
+    }
+
+    #[test]
+    fn movement_hash_is_optional() {
+        let replay = Replay::from_str("VERSION 0.1.0\nLEVEL test.lvl\nLEVEL_HASH 1f\nINPUT 3 -").unwrap();
+
+        assert_eq!(replay.get_level_hash(), 0x1f);
+        assert_eq!(replay.get_movement_hash(), None);
+        assert_eq!(replay.len(), 3);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(Replay::from_str("LEVEL_HASH xyz").is_none());
+        assert!(Replay::from_str("MOVEMENT_HASH").is_none());
+        assert!(Replay::from_str("INPUT many MoveLeft").is_none());
+        assert!(Replay::from_str("INPUT -1 MoveLeft").is_none());
+        assert!(Replay::from_str("INPUT 1 Fly").is_none());
+        assert!(Replay::from_str("SPEED 2").is_none());
+    }
+
+    #[test]
+    fn rejects_replays_that_are_too_long() {
+        assert!(Replay::from_str(&format!("INPUT {} -", MAX_TICKS)).is_some());
+        assert!(Replay::from_str(&format!("INPUT {} -", MAX_TICKS + 1)).is_none());
+        assert!(Replay::from_str(&format!("INPUT {} -\nINPUT 1 -", MAX_TICKS)).is_none());
+        assert!(Replay::from_str("INPUT 18446744073709551615 -").is_none());
+    }
+
+    #[test]
+    fn stops_recording_at_the_limit() {
+        let mut replay = Replay::new("test.lvl", 0, 0);
+        for _ in 0..MAX_TICKS + 10 {
+            replay.record(&[]);
+        }
+
+        assert_eq!(replay.len(), MAX_TICKS);
+    }
+}
@@ -1,200 +1,755 @@
 
-use std::collections::{HashSet};
-
+use std;
+use std::any::Any;
+use std::thread::{self, JoinHandle};
 use trap::{Vector2, Vector2i};
 
 use rax::Game;
 use rax::Renderer;
-use rax::{KeyCode, MouseButton};
+use rax::actions::ActionMap;
+use rax::scene::{Scene, Transition};
+use rax::input::Input;
+use rax::view::View;
 use rax::collision::*;
 
 use frame_counter::FrameCounter;
+use controls::{self, Controls};
+use camera::Camera;
 
-use player::{Player, PlayerCommand};
+use player::{PlayerCommand, PlayerEvent, RagDoll};
 use tile_map::{TileMap, Tile};
+use level_generator::LevelGenerator;
+use simulation::{self, Simulation};
+use replay::{self, Replay};
+use ghost::Ghost;
+use movement_config::{MovementConfigWatcher, MOVEMENT_CONFIG_PATH};
+use animation::{AnimationWatcher, ANIMATION_DIRECTORY};
+use pause::PauseOverlay;
 
 use ::TILE_SIZE;
 
 
+/// How often the movement config file is checked for changes, in seconds
+const CONFIG_POLL_INTERVAL: f64 = 0.5;
+
+/// How many ticks a replay advances every fixed update while fast-forwarding
+const FAST_FORWARD_SPEED: u64 = 8;
+
+pub const DEFAULT_LEVEL_PATH: &str = "levels/tmp.lvl";
+const RANDOM_LEVEL_PATH: &str = "levels/random.lvl";
+
+/// Where replays are saved to and loaded from in game
+const REPLAY_PATH: &str = "replays/last.rpl";
+
+/// Ghosts of the player's best runs, and those of other players, are kept here
+const GHOST_DIRECTORY: &str = "ghosts";
+
+/// Number of ticks the player stays dead before the level restarts
+pub const RESPAWN_DELAY_TICKS: u64 = 2 * simulation::TICK_RATE;
+
+/// Landings faster than the hard landing speed shake the camera, the most at the hardest landing
+/// speed
+const HARD_LANDING_SPEED: f64 = 420.0;
+const HARDEST_LANDING_SPEED: f64 = 700.0;
+
+/// How much the camera shakes when the player dies
+const DEATH_TRAUMA: f64 = 0.8;
+
+
 pub struct RunPlusPlus {
     frame_counter: FrameCounter,
 
-    time: f64,
-    accumulator: f64,
-
     running: bool,
 
-    pressed_keys: HashSet<KeyCode>,
-    window_size: Vector2i,
+    // Keys and mouse buttons bound to actions
+    actions: ActionMap,
+
+    controls: Controls,
+
+    // Commands from key presses, submitted during the next tick
+    pending_commands: Vec<PlayerCommand>,
+
+    camera: Camera,
+
+    // The player's pose before the last tick
+    previous_pose: Option<RagDoll>,
+
+    // The view the last frame was rendered with, to find what is under the mouse
+    view: View,
 
-    player: Player,
-    convex: ConvexHull,
+    simulation: Simulation,
 
-    camera_center: Vector2,
+    // The tick the player died during
+    death_tick: Option<u64>,
 
-    tile_map: TileMap,
+    // Movement parameters, reloaded whenever the file changes
+    movement: MovementConfigWatcher,
+    config_poll_timer: f64,
+
+    // The player's animations, reloaded whenever a file changes
+    animations: AnimationWatcher,
+
+    // The file the level was loaded from
+    level_path: String,
+
+    // Commands submitted since the level was started, None after the level has been edited
+    recording: Option<Replay>,
+
+    // The recording of the last completed run, kept until the next run submits a command
+    last_recording: Option<Replay>,
+
+    // A replay played in place of the keyboard
+    playback: Option<Playback>,
+
+    // The player's pose during every tick since the level was started
+    ghost_recording: Option<Ghost>,
+
+    // The fastest completed run of the level
+    best_ghost: Option<Ghost>,
+
+    // Runs of the level loaded from other files in the ghost directory
+    rival_ghosts: Vec<Ghost>,
+
+    show_ghosts: bool,
+
+    // Generated the current level when playing an endless run
+    generator: Option<LevelGenerator>,
+
+    // The level after the current one in an endless run, generated in the background since
+    // checking that it can be completed takes a while
+    next_level: Option<(LevelGenerator, JoinHandle<TileMap>)>,
+
+    // A change to make to the scene stack, such as pausing
+    transition: Option<Transition>,
+}
+
+
+/// The state of a replay being played
+struct Playback {
+    replay: Replay,
+
+    paused: bool,
+
+    // Advance a single tick while paused
+    step: bool,
 }
 
 
 impl RunPlusPlus {
     pub fn new() -> Self {
-        let tile_map = if let Some(tile_map) = TileMap::open("levels/tmp.lvl", TILE_SIZE) {
-            println!("Loaded map!");
-            tile_map
-        } else {
-            println!("Failed to load map!");
-            TileMap::new(64.0)
-        };
-
-        RunPlusPlus {
-            frame_counter: FrameCounter::new(),
+        RunPlusPlus::open(DEFAULT_LEVEL_PATH)
+    }
 
-            time: 0.0,
-            accumulator: 0.0,
 
-            running: true,
+    /// Start playing a level file
+    pub fn open(level_path: &str) -> Self {
+        let tile_map = match TileMap::open(level_path, TILE_SIZE) {
+            Ok(tile_map) => {
+                println!("Loaded map!");
+                tile_map
+            }
+            Err(e) => {
+                println!("Failed to load map {}: {}", level_path, e);
+                TileMap::new(64.0)
+            }
+        };
 
-            pressed_keys: HashSet::new(),
-            window_size: Vector2i::new(1, 1),
+        let mut game = RunPlusPlus::with_tile_map(level_path, tile_map);
 
-            player: tile_map.spawn_player(),
-            convex: ConvexHull::from_points(&[
+        if level_path == DEFAULT_LEVEL_PATH {
+            game.simulation.add_obstacle(ConvexHull::from_points(&[
                 Vector2::new(300.0 - 400.0, 200.0 + 200.0),
                 Vector2::new(500.0 - 400.0, 200.0 + 250.0),
                 Vector2::new(400.0 - 400.0, 200.0 + 400.0),
                 Vector2::new(300.0 - 400.0, 200.0 + 450.0),
                 Vector2::new(250.0 - 400.0, 200.0 + 300.0),
-            ]),
+            ]));
+            game.restart_level();
+        }
+
+        game
+    }
+
+
+    /// Play a replay file, on the level it was recorded on
+    pub fn from_replay(path: &str) -> Option<Self> {
+        let replay = if let Some(replay) = Replay::open(path) {
+            replay
+        } else {
+            println!("Failed to load replay '{}'", path);
+            return None;
+        };
+
+        let mut game = RunPlusPlus::open(replay.get_level_path());
+        game.play_replay(replay);
+        Some(game)
+    }
+
+
+    /// Start an endless run of randomly generated levels
+    pub fn endless(seed: u64) -> Self {
+        let generator = LevelGenerator::new(seed)
+            .with_length(80)
+            .with_difficulty(0.1);
+
+        let mut game = RunPlusPlus::with_tile_map(RANDOM_LEVEL_PATH, TileMap::new(TILE_SIZE));
+        let generator = generator.with_movement_config(game.movement.get_config());
+        let tile_map = generator.generate(TILE_SIZE);
+        game.start_random_level(generator, tile_map);
+        game
+    }
+
+
+    /// Start playing a tile map, loaded from a level file
+    pub fn with_tile_map(level_path: &str, tile_map: TileMap) -> Self {
+        let mut game = RunPlusPlus {
+            frame_counter: FrameCounter::new(),
+
+            running: true,
+
+            actions: controls::load_bindings(),
 
-            camera_center: Vector2::new(0.0, 0.0),
+            controls: controls::PRESETS[0],
 
-            tile_map,
+            pending_commands: Vec::new(),
+
+            camera: Camera::new(Vector2::new(0.0, 0.0)),
+            previous_pose: None,
+            view: View::new(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0)),
+
+            simulation: Simulation::new(tile_map),
+            death_tick: None,
+
+            movement: MovementConfigWatcher::new(MOVEMENT_CONFIG_PATH),
+            config_poll_timer: 0.0,
+
+            animations: AnimationWatcher::new(ANIMATION_DIRECTORY),
+
+            level_path: level_path.to_owned(),
+
+            recording: None,
+            last_recording: None,
+            playback: None,
+
+            ghost_recording: None,
+            best_ghost: None,
+            rival_ghosts: Vec::new(),
+            show_ghosts: true,
+
+            generator: None,
+            next_level: None,
+
+            transition: None,
+        };
+
+        game.simulation.set_movement_config(game.movement.get_config());
+        game.simulation.set_animations(game.animations.get_animations().clone());
+        game.restart_level();
+        game.camera.jump_to(game.simulation.get_player().get_center());
+        game
+    }
+
+
+    /// Called when the player reaches the exit
+    fn complete_level(&mut self) {
+        println!("Level completed in {:.2} s", self.simulation.get_tick() as f64 * simulation::TICK_TIME);
+
+        if let Some(recording) = self.recording.take() {
+            self.last_recording = Some(recording);
+        }
+
+        if let Some(ghost) = self.ghost_recording.take() {
+            let is_best = match self.best_ghost {
+                Some(ref best) => ghost.len() < best.len(),
+                None => true,
+            };
+
+            if is_best {
+                println!("New best time!");
+
+                let path = self.best_ghost_path();
+                if let Err(e) = std::fs::create_dir_all(GHOST_DIRECTORY).and_then(|_| ghost.save(&path)) {
+                    println!("Failed to save ghost: {}", e);
+                }
+
+                self.best_ghost = Some(ghost);
+            }
+        }
+
+        if let Some((generator, next_level)) = self.next_level.take() {
+            // Waits for the level if it is not done yet
+            let tile_map = next_level.join().unwrap_or_else(|_| generator.generate(TILE_SIZE));
+            self.start_random_level(generator, tile_map);
+        } else {
+            self.restart_level();
         }
     }
-}
 
 
-impl Game for RunPlusPlus {
-    fn update(&mut self, dt: f64) {
-        self.time += dt;
-        self.accumulator += dt;
+    /// Starts a level of an endless run, and starts generating the one after it
+    fn start_random_level(&mut self, generator: LevelGenerator, tile_map: TileMap) {
+        self.simulation = Simulation::new(tile_map);
+        self.simulation.set_movement_config(self.movement.get_config());
+        self.simulation.set_animations(self.animations.get_animations().clone());
+        self.generator = Some(generator);
+        self.save_random_level();
+
+        self.restart_level();
+        self.camera.jump_to(self.simulation.get_player().get_center());
+
+        let generator = self.generator.as_ref().unwrap();
+        let next = generator.clone()
+            .with_seed(generator.get_seed().wrapping_add(1))
+            .with_difficulty(generator.get_difficulty() + 0.05)
+            .with_movement_config(self.movement.get_config());
+
+        let background = next.clone();
+        self.next_level = Some((next, thread::spawn(move || background.generate(TILE_SIZE))));
+    }
+
+
+    /// Moves the player back to the start of the level
+    fn restart_level(&mut self) {
+        self.simulation.restart();
+        self.pending_commands.clear();
+        self.death_tick = None;
+
+        self.camera.set_bounds(level_bounds(self.simulation.get_tile_map()));
 
-        let target_frame_time = 1.0 / 240.0;
-        while self.accumulator > target_frame_time {
-            let dt = target_frame_time;
+        if let Some(ref mut playback) = self.playback {
+            playback.paused = false;
+        } else {
+            let (level_hash, movement_hash) = (self.simulation.level_hash(), self.simulation.movement_hash());
+            self.recording = Some(Replay::new(&self.level_path, level_hash, movement_hash));
+            self.ghost_recording = Some(Ghost::new(level_hash, movement_hash));
+        }
+
+        self.load_ghosts();
+    }
 
-            if self.pressed_keys.contains(&KeyCode::A) { self.player.submit_command(PlayerCommand::MoveLeft); }
-            if self.pressed_keys.contains(&KeyCode::D) { self.player.submit_command(PlayerCommand::MoveRight); }
 
-            if self.pressed_keys.contains(&KeyCode::S) {
-                if self.pressed_keys.contains(&KeyCode::A) || self.pressed_keys.contains(&KeyCode::D) {
-                    self.player.submit_command(PlayerCommand::Slide);
+    /// Advances the level by one tick, with commands from either the keyboard or the replay
+    fn tick(&mut self, input: &Input) {
+        self.previous_pose = Some(self.simulation.get_player().get_pose().clone());
+
+        let commands = if let Some(ref mut playback) = self.playback {
+            if let Some(commands) = playback.replay.get_commands(self.simulation.get_tick()) {
+                commands.to_vec()
+            } else {
+                if !playback.paused {
+                    println!("Replay finished after {} ticks, state hash {:016x}", self.simulation.get_tick(), self.simulation.state_hash());
+                    playback.paused = true;
                 }
+                return;
+            }
+        } else {
+            let mut commands = self.pending_commands.split_off(0);
+            commands.extend(self.controls.held_commands(&self.actions, input));
+            commands
+        };
+
+        if let Some(ref mut recording) = self.recording {
+            recording.record(&commands);
+
+            if !commands.is_empty() {
+                self.last_recording = None;
             }
+        }
+
+        let fall_speed = self.simulation.get_player().get_velocity().y;
+
+        self.simulation.step(&commands);
+
+        for event in self.simulation.get_player().get_events().to_vec() {
+            self.on_player_event(event, fall_speed);
+        }
 
-            self.player.update(dt, &[&self.tile_map, &self.convex]);
+        if let Some(ref mut ghost) = self.ghost_recording {
+            ghost.record(self.simulation.get_player());
+        }
 
-            self.camera_center += (self.player.get_center() - self.camera_center) * dt * 4.0;
-            // self.camera_center = (self.player.get_center());
+        // A replay ends at the exit, so it is left to finish instead of moving on to the next level
+        if self.playback.is_none() && self.simulation.is_at_exit(self.simulation.get_state()) {
+            self.complete_level();
+        }
 
-            self.accumulator -= target_frame_time;
-            if self.accumulator > 1.0 {
-                self.accumulator = 0.0;
+        if let Some(tick) = self.death_tick {
+            if self.playback.is_none() && self.simulation.get_tick() >= tick + RESPAWN_DELAY_TICKS {
+                self.restart_level();
             }
         }
+
+        let player = self.simulation.get_player();
+        self.camera.update(simulation::TICK_TIME, player.get_drawn_center(), player.get_velocity(), player.is_grounded());
     }
 
-    fn render(&mut self, renderer: &mut Renderer) {
-        if let Some(fps) = self.frame_counter.tick() {
-            println!("FPS: {}", fps.round());
+
+    /// Reacts to something that happened to the player during the last tick, falling at a speed
+    /// before it
+    fn on_player_event(&mut self, event: PlayerEvent, fall_speed: f64) {
+        match event {
+            PlayerEvent::Died => {
+                self.death_tick = Some(self.simulation.get_tick());
+                self.camera.add_trauma(DEATH_TRAUMA);
+            },
+
+            PlayerEvent::Landed => {
+                if fall_speed > HARD_LANDING_SPEED {
+                    self.camera.add_trauma((fall_speed - HARD_LANDING_SPEED) / (HARDEST_LANDING_SPEED - HARD_LANDING_SPEED));
+                }
+            },
+
+            _ => ()
         }
+    }
 
-        renderer.set_center(self.camera_center);
 
-        renderer.clear(0.2, 0.2, 0.2);
+    /// Loads every ghost that was recorded on the current level
+    fn load_ghosts(&mut self) {
+        let level_hash = self.simulation.level_hash();
+        let movement_hash = self.simulation.movement_hash();
+        let best_path = self.best_ghost_path();
 
-        renderer.color = [0.0, 1.0, 0.0, 1.0];
-        renderer.fill_convex(self.convex.get_points());
+        self.best_ghost = None;
+        self.rival_ghosts.clear();
 
+        let entries = if let Ok(entries) = std::fs::read_dir(GHOST_DIRECTORY) {
+            entries
+        } else {
+            return;
+        };
 
-        renderer.color = [0.03, 0.03, 0.03, 1.0];
-        self.tile_map.draw_shadows(renderer, self.player.get_center());
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().map_or(true, |extension| extension != "ghost") {
+                continue;
+            }
+
+            if let Some(ghost) = Ghost::open(&path) {
+                if ghost.get_level_hash() != level_hash {
+                    continue;
+                }
+
+                if ghost.get_movement_hash() != movement_hash {
+                    println!("Ghost {} was recorded with different movement parameters", path.display());
+                }
+
+                if path == std::path::Path::new(&best_path) {
+                    self.best_ghost = Some(ghost);
+                } else {
+                    self.rival_ghosts.push(ghost);
+                }
+            }
+        }
+    }
 
-        self.tile_map.draw(renderer);
 
-        self.player.draw(renderer);
+    /// Returns where the ghost of the best run through the current level is saved
+    fn best_ghost_path(&self) -> String {
+        format!("{}/best_{:016x}.ghost", GHOST_DIRECTORY, self.simulation.level_hash())
     }
 
-    fn is_running(&self) -> bool {
-        self.running
+
+    /// Plays a replay from the start of the current level
+    fn play_replay(&mut self, replay: Replay) {
+        if replay.get_version() != replay::VERSION {
+            println!("Replay was recorded with version {}, this is version {}", replay.get_version(), replay::VERSION);
+        }
+        if replay.get_level_hash() != self.simulation.level_hash() {
+            println!("Replay was recorded on a different version of '{}', it will not play back the same", replay.get_level_path());
+        }
+        if replay.get_movement_hash().map_or(false, |hash| hash != self.simulation.movement_hash()) {
+            println!("Replay was recorded with different movement parameters, it will not play back the same");
+        }
+
+        println!("Playing replay of {} ticks: P to pause, hold F to fast-forward, '.' to step, F8 to stop", replay.len());
+
+        self.playback = Some(Playback {
+            replay,
+            paused: false,
+            step: false,
+        });
+        self.recording = None;
+        self.ghost_recording = None;
+
+        self.restart_level();
+    }
+
+
+    /// Stops playing a replay and gives control back to the keyboard
+    fn stop_replay(&mut self) {
+        if self.playback.take().is_some() {
+            println!("Stopped replay");
+            self.restart_level();
+        }
     }
 
-    fn on_key_press(&mut self, key: KeyCode) {
-        match key {
-            KeyCode::R => {
-                self.player = Player::new(Vector2::new(0.0, 0.0));
+
+    /// Writes the commands of the last completed run, or those submitted since the level was
+    /// started, to disk
+    fn save_recording(&self) {
+        if let Some(recording) = self.last_recording.as_ref().or(self.recording.as_ref()) {
+            if let Err(e) = std::fs::create_dir_all("replays").and_then(|_| recording.save(REPLAY_PATH)) {
+                println!("Failed to save replay: {}", e);
+            } else {
+                println!("Saved replay of {} ticks to {}", recording.len(), REPLAY_PATH);
             }
+        } else {
+            println!("Nothing to save, the level was edited since it was started");
+        }
+    }
 
-            KeyCode::Space => self.player.submit_command(PlayerCommand::Jump),
 
-            KeyCode::S => {
-                if !self.pressed_keys.contains(&KeyCode::A) && !self.pressed_keys.contains(&KeyCode::D) {
-                    self.player.submit_command(PlayerCommand::Drop)
+    /// Reacts to an action that was just started by a key or mouse button
+    fn on_action_start(&mut self, action: &str, input: &Input) {
+        if let Some(ref mut playback) = self.playback {
+            match action {
+                "PAUSE_REPLAY" => playback.paused = !playback.paused,
+                "STEP_REPLAY" => {
+                    playback.paused = true;
+                    playback.step = true;
+                },
+
+                _ => ()
+            }
+        }
+
+        match action {
+            "RESTART" => self.restart_level(),
+
+            "SAVE_REPLAY" => self.save_recording(),
+            "LOAD_REPLAY" => {
+                if let Some(replay) = Replay::open(REPLAY_PATH) {
+                    self.play_replay(replay);
+                } else {
+                    println!("Failed to load replay '{}'", REPLAY_PATH);
                 }
             },
+            "STOP_REPLAY" => self.stop_replay(),
+
+            "TOGGLE_GHOSTS" => self.show_ghosts = !self.show_ghosts,
 
-            KeyCode::F5 => self.tile_map.save("levels/tmp.lvl").unwrap_or_else(|e|{println!("{}", e)}),
+            "PAUSE" => self.transition = Some(Transition::Push(Box::new(PauseOverlay::new(self.actions.clone())))),
+
+            "SAVE_LEVEL" => self.simulation.get_tile_map().save("levels/tmp.lvl").unwrap_or_else(|e|{println!("{}", e)}),
 
             _ => ()
         }
 
-        self.pressed_keys.insert(key);
+        if let Some(command) = self.controls.start_command(action, &self.actions, input) {
+            self.pending_commands.push(command);
+        }
+    }
+
+
+    /// Reacts to an action that was just stopped
+    fn on_action_stop(&mut self, action: &str) {
+        if let Some(command) = self.controls.stop_command(action) {
+            self.pending_commands.push(command);
+        }
     }
 
-    fn on_key_release(&mut self, key: KeyCode) {
-        self.pressed_keys.remove(&key);
 
-        match key {
-            KeyCode::Space => self.player.submit_command(PlayerCommand::StopJump),
+    /// Places a tile of the type picked by the held tile actions at a point on the screen, or
+    /// removes the tile there. While the dash pickup action is held, a dash pickup is placed or
+    /// removed instead.
+    fn edit_tile(&mut self, point: Vector2i, place: bool, input: &Input) {
+        // A recording is only valid for the level it was started on
+        if self.recording.take().is_some() {
+            println!("Level edited, recording stopped until the level is restarted");
+        }
+        self.last_recording = None;
+        self.ghost_recording = None;
+
+        let tile = [
+            ("TILE_WEDGE_UP_LEFT", Tile::WedgeUpLeft),
+            ("TILE_WEDGE_UP_RIGHT", Tile::WedgeUpRight),
+            ("TILE_WEDGE_DOWN_LEFT", Tile::WedgeDownLeft),
+            ("TILE_WEDGE_DOWN_RIGHT", Tile::WedgeDownRight),
+            ("TILE_SLANT_UP_LEFT", Tile::SlantUpLeft),
+            ("TILE_SLANTED_WEDGE_UP_LEFT", Tile::SlantedWedgeUpLeft),
+        ].iter().find(|&&(action, _)| self.actions.is_active(action, input)).map_or(Tile::Square, |&(_, tile)| tile);
+
+        let position = self.simulation.get_tile_map().world_to_tile(self.view.screen_to_world(point));
+
+        if self.actions.is_active("DASH_PICKUP", input) {
+            if place {
+                self.simulation.add_dash_pickup(position)
+            } else {
+                self.simulation.remove_dash_pickup(position)
+            }
+            return;
+        }
 
-            _ => ()
+        {
+            let tile_map = self.simulation.get_tile_map_mut();
+            if place {
+                tile_map.add_tile(position, tile)
+            } else {
+                tile_map.remove_tile(position)
+            }
         }
+
+        self.camera.set_bounds(level_bounds(self.simulation.get_tile_map()));
     }
 
-    fn on_mouse_press(&mut self, button: MouseButton, x: u64, y: u64) {
-        let tile_size = self.tile_map.get_tile_size();
 
-        let world_x = x as f64 + self.camera_center.x - self.window_size.x as f64 / 2.0;
-        let world_y = y as f64 + self.camera_center.y - self.window_size.y as f64 / 2.0;
+    /// Keeps a copy of the current random level so that it can be played again
+    fn save_random_level(&self) {
+        if let Some(ref generator) = self.generator {
+            println!("Generated level with seed {}", generator.get_seed());
+        }
 
-        let bx = (world_x / tile_size).floor() as i64;
-        let by = (world_y / tile_size).floor() as i64;
+        self.simulation.get_tile_map().save(RANDOM_LEVEL_PATH).unwrap_or_else(|e| { println!("{}", e) });
+    }
+}
 
-        if button == MouseButton::Left {
-            if self.pressed_keys.contains(&KeyCode::Key1) {
-                self.tile_map.add_tile([bx, by].into(), Tile::WedgeUpLeft)
-            } else if self.pressed_keys.contains(&KeyCode::Key2) {
-                self.tile_map.add_tile([bx, by].into(), Tile::WedgeUpRight)
-            } else if self.pressed_keys.contains(&KeyCode::Key3) {
-                self.tile_map.add_tile([bx, by].into(), Tile::WedgeDownLeft)
-            } else if self.pressed_keys.contains(&KeyCode::Key4) {
-                self.tile_map.add_tile([bx, by].into(), Tile::WedgeDownRight)
-            } else if self.pressed_keys.contains(&KeyCode::Key5) {
-                self.tile_map.add_tile([bx, by].into(), Tile::SlantUpLeft)
-            } else if self.pressed_keys.contains(&KeyCode::Key6) {
-                self.tile_map.add_tile([bx, by].into(), Tile::SlantedWedgeUpLeft)
-            } else {
-                self.tile_map.add_tile([bx, by].into(), Tile::Square)
+
+impl Game for RunPlusPlus {
+    fn update(&mut self, dt: f64, input: &Input) {
+        for action in self.actions.get_started(input) {
+            match action.as_str() {
+                "PLACE_TILE" => self.edit_tile(input.get_cursor_position(), true, input),
+                "REMOVE_TILE" => self.edit_tile(input.get_cursor_position(), false, input),
+
+                action => self.on_action_start(action, input),
+            }
+        }
+        for action in self.actions.get_stopped(input) {
+            self.on_action_stop(&action);
+        }
+
+        self.config_poll_timer += dt;
+        if self.config_poll_timer >= CONFIG_POLL_INTERVAL {
+            self.config_poll_timer = 0.0;
+
+            if self.movement.poll() {
+                println!("Reloaded {}", MOVEMENT_CONFIG_PATH);
+                self.simulation.set_movement_config(self.movement.get_config());
+
+                // Runs with different movement are not comparable
+                if self.recording.take().is_some() {
+                    println!("Movement changed, recording stopped until the level is restarted");
+                }
+                self.ghost_recording = None;
+            }
+
+            // Animations only change how the player is drawn, so recordings carry on
+            if self.animations.poll() {
+                println!("Reloaded {}", ANIMATION_DIRECTORY);
+                self.simulation.set_animations(self.animations.get_animations().clone());
+            }
+        }
+
+        let step = match self.playback {
+            Some(ref mut playback) => std::mem::replace(&mut playback.step, false),
+            None => false,
+        };
+        if step {
+            self.tick(input);
+        }
+    }
+
+    fn fixed_update(&mut self, _dt: f64, input: &Input) {
+        // The simulation only ever advances in whole ticks, one every fixed update
+        let ticks = match self.playback {
+            Some(ref playback) if playback.paused => 0,
+            Some(_) if self.actions.is_active("FAST_FORWARD", input) => FAST_FORWARD_SPEED,
+            _ => 1,
+        };
+
+        for _ in 0..ticks {
+            self.tick(input);
+        }
+    }
+
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64) {
+        if let Some(fps) = self.frame_counter.tick() {
+            println!("FPS: {}", fps.round());
+        }
+
+        // Nothing moves while paused, so the last tick is drawn as it is
+        let alpha = match self.playback {
+            Some(ref playback) if playback.paused => 1.0,
+            _ => alpha,
+        };
+
+        renderer.set_center(self.camera.get_center(alpha));
+        renderer.set_rotation(self.camera.get_rotation(alpha));
+        self.view = renderer.get_view();
+
+        renderer.clear(0.2, 0.2, 0.2);
+
+        renderer.color = [0.0, 1.0, 0.0, 1.0];
+        for obstacle in self.simulation.get_obstacles() {
+            renderer.fill_convex(obstacle.get_points());
+        }
+
+        let tile_map = self.simulation.get_tile_map();
+        let player = self.simulation.get_player();
+
+        renderer.color = [0.03, 0.03, 0.03, 1.0];
+        tile_map.draw_shadows(renderer, player.get_drawn_center());
+
+        tile_map.draw(renderer);
+
+        // Dash pickups, faded while they are respawning
+        let pickups = tile_map.get_dash_pickups().iter().zip(self.simulation.get_state().pickup_timers.iter());
+        for (pickup, &timer) in pickups {
+            renderer.color = if timer == 0 { [0.3, 0.9, 1.0, 0.8] } else { [0.3, 0.9, 1.0, 0.15] };
+            tile_map.draw_dash_pickup(renderer, *pickup);
+        }
+
+        if self.show_ghosts {
+            let tick = self.simulation.get_tick();
+
+            for ghost in self.rival_ghosts.iter() {
+                ghost.draw(renderer, tick, [1.0, 0.6, 0.2, 0.4]);
+            }
+            if let Some(ref ghost) = self.best_ghost {
+                ghost.draw(renderer, tick, [0.4, 0.8, 1.0, 0.4]);
             }
-        } else if button == MouseButton::Right {
-            self.tile_map.remove_tile([bx, by].into())
         }
+
+        // Drawn between the last two ticks, so that it moves smoothly at any frame rate
+        let previous_pose = self.previous_pose.as_ref().unwrap_or(player.get_pose());
+        player.draw(renderer, previous_pose, alpha);
     }
 
+    fn is_running(&self) -> bool {
+        self.running
+    }
 
     fn on_size_change(&mut self, width: u64, height: u64) {
-        self.window_size = Vector2i::new(width as i64, height as i64);
+        self.camera.set_view_size(width as f64, height as f64);
+    }
+}
+
+
+impl Scene for RunPlusPlus {
+    fn take_transition(&mut self) -> Option<Transition> {
+        self.transition.take()
     }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+
+/// Returns the area covered by the tiles of a level, or None if there are none
+fn level_bounds(tile_map: &TileMap) -> Option<AABB> {
+    if tile_map.is_empty() {
+        return None;
+    }
+
+    // The bounding box reaches the corner of the last tiles, not past them
+    let tile_size = tile_map.get_tile_size();
+    let mut bounds = tile_map.bounding_box();
+    bounds.right += tile_size;
+    bounds.bottom += tile_size;
+
+    Some(bounds)
 }
 
 
@@ -0,0 +1,305 @@
+use std::hash::{Hash, Hasher};
+
+use trap::Vector2i;
+
+use rax::collision::*;
+
+use player::{Player, PlayerCommand};
+use tile_map::TileMap;
+use movement_config::MovementConfig;
+use fnv::Fnv1a;
+use animation::AnimationSet;
+
+
+/// Number of simulation steps per second
+pub const TICK_RATE: u64 = 240;
+
+/// Duration of a single simulation step
+pub const TICK_TIME: f64 = 1.0 / TICK_RATE as f64;
+
+/// Number of ticks before a dash pickup comes back after being used
+pub const DASH_PICKUP_RESPAWN_TICKS: u32 = 3 * TICK_RATE as u32;
+
+/// Number of tiles the player can fall below the lowest tile before dying
+const DEATH_DEPTH: f64 = 4.0;
+
+
+/// Everything that changes while a level is played
+#[derive(Clone)]
+pub struct State {
+    /// Number of ticks since the level was started
+    pub tick: u64,
+
+    pub player: Player,
+
+    /// Ticks left until each of the level's dash pickups can be used again, zero if available
+    pub pickup_timers: Vec<u32>,
+}
+
+
+/// Steps a level forward in fixed ticks.
+///
+/// A step only depends on the current state and the commands for that tick, so playing the same
+/// commands from the same state always ends up in the same state.
+pub struct Simulation {
+    tile_map: TileMap,
+    obstacles: Vec<ConvexHull>,
+
+    movement: MovementConfig,
+
+    // Only changes how the player is drawn
+    animations: AnimationSet,
+
+    state: State,
+}
+
+
+impl Simulation {
+    pub fn new(tile_map: TileMap) -> Simulation {
+        let animations = AnimationSet::new();
+        let state = State::new(&tile_map, &animations);
+
+        Simulation {
+            tile_map,
+            obstacles: Vec::new(),
+
+            movement: MovementConfig::new(),
+
+            animations,
+
+            state,
+        }
+    }
+
+
+    /// Adds an obstacle the player collides with, in addition to the tile map
+    pub fn add_obstacle(&mut self, obstacle: ConvexHull) {
+        self.obstacles.push(obstacle);
+    }
+
+
+    /// Changes how the player moves from the next tick on
+    pub fn set_movement_config(&mut self, movement: MovementConfig) {
+        self.movement = movement;
+    }
+
+    pub fn get_movement_config(&self) -> &MovementConfig {
+        &self.movement
+    }
+
+
+    pub fn set_animations(&mut self, animations: AnimationSet) {
+        self.animations = animations;
+    }
+
+
+    /// Advances the simulation by one tick
+    pub fn step(&mut self, commands: &[PlayerCommand]) {
+        Simulation::advance(&self.tile_map, &self.obstacles, &self.movement, &self.animations, &mut self.state, commands);
+    }
+
+
+    /// Advances a state, other than the current one, by one tick in this level
+    pub fn step_state(&self, state: &mut State, commands: &[PlayerCommand]) {
+        Simulation::advance(&self.tile_map, &self.obstacles, &self.movement, &self.animations, state, commands);
+    }
+
+
+    fn advance(tile_map: &TileMap, obstacles: &[ConvexHull], movement: &MovementConfig, animations: &AnimationSet, state: &mut State, commands: &[PlayerCommand]) {
+        for command in commands {
+            state.player.submit_command(*command);
+        }
+
+        let mut colliders: Vec<&Collide<ConvexHull>> = vec![tile_map];
+        for obstacle in obstacles {
+            colliders.push(obstacle);
+        }
+
+        state.player.update(TICK_TIME, movement, animations, tile_map, &colliders);
+        state.tick += 1;
+
+        let tile_size = tile_map.get_tile_size();
+        let bounds = state.player.get_bounding_box();
+
+        if !tile_map.is_empty() && bounds.top > tile_map.bounding_box().bottom + DEATH_DEPTH * tile_size {
+            state.player.kill();
+        }
+
+        for (pickup, timer) in tile_map.get_dash_pickups().iter().zip(state.pickup_timers.iter_mut()) {
+            if *timer > 0 {
+                *timer -= 1;
+                continue;
+            }
+
+            let left = pickup.x as f64 * tile_size;
+            let top = pickup.y as f64 * tile_size;
+            let area = AABB::new(left, left + tile_size, top, top + tile_size);
+
+            // Pickups are only used up when they would give back a dash
+            if state.player.get_dash_charges() < movement.dash_charges && bounds.intersects(&area) {
+                state.player.refill_dash(movement);
+                *timer = DASH_PICKUP_RESPAWN_TICKS;
+            }
+        }
+    }
+
+
+    /// Moves the player back to the start of the level
+    pub fn restart(&mut self) {
+        self.state = State::new(&self.tile_map, &self.animations);
+    }
+
+
+    pub fn get_state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+
+
+    pub fn get_tick(&self) -> u64 {
+        self.state.tick
+    }
+
+    pub fn get_player(&self) -> &Player {
+        &self.state.player
+    }
+
+
+    pub fn get_tile_map(&self) -> &TileMap {
+        &self.tile_map
+    }
+
+    pub fn get_tile_map_mut(&mut self) -> &mut TileMap {
+        &mut self.tile_map
+    }
+
+
+    /// Adds a dash pickup to the level, ready to be used straight away
+    pub fn add_dash_pickup(&mut self, pos: Vector2i) {
+        if !self.tile_map.get_dash_pickups().contains(&pos) {
+            self.tile_map.add_dash_pickup(pos);
+            self.state.pickup_timers.push(0);
+        }
+    }
+
+
+    /// Removes the dash pickup in a tile from the level
+    pub fn remove_dash_pickup(&mut self, pos: Vector2i) {
+        if let Some(index) = self.tile_map.get_dash_pickups().iter().position(|pickup| *pickup == pos) {
+            self.tile_map.remove_dash_pickup(pos);
+            self.state.pickup_timers.remove(index);
+        }
+    }
+
+
+    pub fn get_obstacles(&self) -> &[ConvexHull] {
+        &self.obstacles
+    }
+
+
+    /// Returns true if the player is alive and inside the exit of a state
+    pub fn is_at_exit(&self, state: &State) -> bool {
+        if state.player.is_dead() {
+            return false;
+        }
+
+        let cell = self.tile_map.world_to_tile(state.player.get_center());
+        Some(cell) == self.tile_map.get_exit()
+    }
+
+
+    /// Returns a hash of the current state, equal for any two runs that are in the same state
+    pub fn state_hash(&self) -> u64 {
+        self.state.hash_value()
+    }
+
+
+    /// Returns a hash of the level being simulated: the tile map and the obstacles. It identifies
+    /// the level in replays and ghosts, so it is the same on every platform and version of Rust.
+    pub fn level_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        let tile_map = &self.tile_map;
+
+        hasher.write_u64(tile_map.get_tile_size().to_bits());
+
+        let start = tile_map.get_player_start();
+        hasher.write_i64(start.x);
+        hasher.write_i64(start.y);
+
+        if let Some(exit) = tile_map.get_exit() {
+            hasher.write_u8(1);
+            hasher.write_i64(exit.x);
+            hasher.write_i64(exit.y);
+        } else {
+            hasher.write_u8(0);
+        }
+
+        hasher.write_usize(tile_map.get_dash_pickups().len());
+        for pickup in tile_map.get_dash_pickups() {
+            hasher.write_i64(pickup.x);
+            hasher.write_i64(pickup.y);
+        }
+
+        // Tiles are sorted and written by name, which does not change with the order of the enum
+        let tiles = tile_map.get_tiles();
+        hasher.write_usize(tiles.len());
+        for (pos, tile) in tiles {
+            hasher.write_i64(pos.x);
+            hasher.write_i64(pos.y);
+            hasher.write(tile.to_string().as_bytes());
+            hasher.write_u8(0);
+        }
+
+        hasher.write_usize(self.obstacles.len());
+        for obstacle in self.obstacles.iter() {
+            hasher.write_usize(obstacle.get_points().len());
+            for point in obstacle.get_points() {
+                hasher.write_u64(point.x.to_bits());
+                hasher.write_u64(point.y.to_bits());
+            }
+        }
+
+        hasher.finish()
+    }
+
+
+    /// Returns a hash of the movement parameters, saved alongside the level hash since a run only
+    /// plays back the same with the same parameters
+    pub fn movement_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        self.movement.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+impl State {
+    /// The state at the start of a level
+    pub fn new(tile_map: &TileMap, animations: &AnimationSet) -> State {
+        State {
+            tick: 0,
+            player: tile_map.spawn_player(animations),
+            pickup_timers: vec![0; tile_map.get_dash_pickups().len()],
+        }
+    }
+
+
+    /// Returns a hash of the state, which is the same on every platform and version of Rust, so
+    /// that it can be compared with hashes of earlier runs
+    pub fn hash_value(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+
+        hasher.write_u64(self.tick);
+        self.player.write_hash(&mut hasher);
+
+        hasher.write_usize(self.pickup_timers.len());
+        for &timer in self.pickup_timers.iter() {
+            hasher.write_u32(timer);
+        }
+
+        hasher.finish()
+    }
+}
@@ -0,0 +1,449 @@
+use std;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+use trap::{Vector2, Vector2i};
+
+use player::{Player, PlayerCommand};
+use tile_map::TileMap;
+use simulation::{State, Simulation};
+use movement_config::MovementConfig;
+
+/// Number of ticks each input is held before the search branches
+const TICKS_PER_STEP: u32 = 12;
+
+/// Size of the cells, in pixels and pixels per second, used to merge similar states
+const POSITION_RESOLUTION: f64 = 4.0;
+const VELOCITY_RESOLUTION: f64 = 20.0;
+
+/// Distance, in pixels, that every tick spent is worth when picking the next state to explore,
+/// so that states that only hover close to the exit, like falling past it, are not explored first
+const TICK_COST: f64 = 1.0;
+
+
+/// Searches for input sequences that take the player through a level
+pub struct Solver {
+    simulation: Simulation,
+
+    // Stop searching after this many states have been expanded
+    max_expansions: usize,
+
+    // Stop searching as soon as a way to the exit has been found
+    stop_at_exit: bool,
+}
+
+
+/// The keys held down during a step of the search
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct HeldInput {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub jump: bool,
+    pub dash: bool,
+}
+
+
+/// The result of a search
+pub struct Report {
+    /// Inputs, and the number of ticks they are held, that take the player to the exit
+    pub witness: Option<Vec<(HeldInput, u32)>>,
+
+    /// Every tile the player's center has been in
+    pub visited_cells: HashSet<Vector2i>,
+
+    /// Every tile the player has stood on
+    pub reached_tiles: HashSet<Vector2i>,
+
+    /// Number of states that were expanded
+    pub expanded: usize,
+
+    /// False if the search stopped, at the exit or out of expansions, before exploring every state
+    pub complete: bool,
+}
+
+
+/// A state reached during the search
+struct Node {
+    state: State,
+    input: HeldInput,
+    ticks: u32,
+
+    parent: Option<usize>,
+}
+
+
+/// A node waiting to be expanded, ordered by its distance to the exit and the time taken to get there
+struct Candidate {
+    priority: f64,
+    index: usize,
+}
+
+
+impl Solver {
+    pub fn new(tile_map: &TileMap, movement: MovementConfig, max_expansions: usize) -> Solver {
+        let mut simulation = Simulation::new(tile_map.clone());
+        simulation.set_movement_config(movement);
+
+        Solver {
+            simulation,
+            max_expansions,
+            stop_at_exit: false,
+        }
+    }
+
+
+    /// Stop the search once the exit is reached, instead of exploring the rest of the level
+    pub fn with_stop_at_exit(self, stop_at_exit: bool) -> Self {
+        Solver { stop_at_exit, .. self }
+    }
+
+
+    /// Explores the level from the player's start
+    pub fn solve(&self) -> Report {
+        let mut report = Report {
+            witness: None,
+            visited_cells: HashSet::new(),
+            reached_tiles: HashSet::new(),
+            expanded: 0,
+            complete: true,
+        };
+
+        let tile_map = self.simulation.get_tile_map();
+
+        let exit = tile_map.get_exit();
+        let tile_size = tile_map.get_tile_size();
+
+        let mut nodes = vec![Node {
+            state: self.simulation.get_state().clone(),
+            input: HeldInput::idle(),
+            ticks: 0,
+            parent: None,
+        }];
+
+        let mut seen = HashSet::new();
+        seen.insert(state_key(&nodes[0].state.player, nodes[0].input));
+
+        let mut frontier = BinaryHeap::new();
+        frontier.push(Candidate { priority: 0.0, index: 0 });
+
+        'search: while let Some(candidate) = frontier.pop() {
+            if report.expanded >= self.max_expansions {
+                report.complete = false;
+                break;
+            }
+            report.expanded += 1;
+
+            for input in HeldInput::all().iter() {
+                let mut state = nodes[candidate.index].state.clone();
+                let mut previous = nodes[candidate.index].input;
+
+                let mut ticks = 0;
+                let mut reached_exit = false;
+                let mut dead = false;
+
+                while ticks < TICKS_PER_STEP {
+                    self.simulation.step_state(&mut state, &input.commands(previous));
+                    previous = *input;
+                    ticks += 1;
+
+                    let player = &state.player;
+                    let cell = tile_map.world_to_tile(player.get_center());
+                    report.visited_cells.insert(cell);
+
+                    if player.is_grounded() {
+                        let bounds = player.get_bounding_box();
+                        let feet = Vector2::new(player.get_center().x, bounds.bottom + 1.0);
+                        let tile = tile_map.world_to_tile(feet);
+                        if tile_map.get_tile(tile).is_some() {
+                            report.reached_tiles.insert(tile);
+                        }
+                    }
+
+                    if Some(cell) == exit {
+                        reached_exit = true;
+                        break;
+                    }
+
+                    if player.is_dead() {
+                        dead = true;
+                        break;
+                    }
+                }
+
+                if dead {
+                    continue;
+                }
+
+                let key = state_key(&state.player, *input);
+                if !reached_exit && !seen.insert(key) {
+                    continue;
+                }
+
+                let priority = match exit {
+                    Some(exit) => {
+                        let target = (Vector2::from(exit) + Vector2::new(0.5, 0.5)) * tile_size;
+                        state.player.get_center().distance(target) + TICK_COST * state.tick as f64
+                    }
+                    None => 0.0,
+                };
+
+                nodes.push(Node {
+                    state,
+                    input: *input,
+                    ticks,
+                    parent: Some(candidate.index),
+                });
+                let index = nodes.len() - 1;
+
+                if reached_exit {
+                    if report.witness.is_none() {
+                        report.witness = Some(witness(&nodes, index));
+                    }
+
+                    if self.stop_at_exit {
+                        report.complete = false;
+                        break 'search;
+                    }
+                } else {
+                    frontier.push(Candidate { priority, index });
+                }
+            }
+        }
+
+        report
+    }
+}
+
+
+/// Walks back from a node to the start, merging consecutive steps with the same input
+fn witness(nodes: &[Node], index: usize) -> Vec<(HeldInput, u32)> {
+    let mut steps: Vec<(HeldInput, u32)> = Vec::new();
+
+    let mut current = Some(index);
+    while let Some(index) = current {
+        let node = &nodes[index];
+        if node.parent.is_some() {
+            steps.push((node.input, node.ticks));
+        }
+        current = node.parent;
+    }
+
+    steps.reverse();
+
+    let mut merged: Vec<(HeldInput, u32)> = Vec::new();
+    for (input, ticks) in steps {
+        if let Some(last) = merged.last_mut() {
+            if last.0 == input {
+                last.1 += ticks;
+                continue;
+            }
+        }
+
+        merged.push((input, ticks));
+    }
+
+    merged
+}
+
+
+/// Returns a discretized version of the player's state, used to detect already explored states
+fn state_key(player: &Player, input: HeldInput) -> (i64, i64, i64, i64, bool, bool, bool, bool, bool, u32, bool, bool) {
+    let center = player.get_center();
+    let velocity = player.get_velocity();
+
+    (
+        (center.x / POSITION_RESOLUTION).round() as i64,
+        (center.y / POSITION_RESOLUTION).round() as i64,
+        (velocity.x / VELOCITY_RESOLUTION).round() as i64,
+        (velocity.y / VELOCITY_RESOLUTION).round() as i64,
+        player.is_grounded(),
+        player.is_on_wall(),
+        player.is_hanging(),
+        player.is_crouching(),
+        player.is_dashing(),
+        player.get_dash_charges(),
+        input.jump,
+        input.down,
+    )
+}
+
+
+impl HeldInput {
+    pub fn idle() -> HeldInput {
+        HeldInput { left: false, right: false, up: false, down: false, jump: false, dash: false }
+    }
+
+
+    /// Returns every input the search tries: standing, running and jumping, crouching, sliding and
+    /// dropping, and dashing in each of the eight directions
+    pub fn all() -> Vec<HeldInput> {
+        let idle = HeldInput::idle();
+        let mut inputs = Vec::new();
+
+        for &(left, right) in [(false, false), (true, false), (false, true)].iter() {
+            for &jump in [false, true].iter() {
+                inputs.push(HeldInput { left, right, jump, .. idle });
+            }
+
+            inputs.push(HeldInput { left, right, down: true, .. idle });
+
+            for &(up, down) in [(true, false), (false, false), (false, true)].iter() {
+                if left || right || up || down {
+                    inputs.push(HeldInput { left, right, up, down, dash: true, .. idle });
+                }
+            }
+        }
+
+        inputs
+    }
+
+
+    /// Returns the commands submitted during a tick, given the input held during the previous tick
+    pub fn commands(&self, previous: HeldInput) -> Vec<PlayerCommand> {
+        let mut commands = Vec::new();
+
+        // The same commands the keyboard controls submit
+        if self.left { commands.push(PlayerCommand::MoveLeft); }
+        if self.right { commands.push(PlayerCommand::MoveRight); }
+
+        if self.down && (self.left || self.right) { commands.push(PlayerCommand::Slide); }
+
+        if self.up { commands.push(PlayerCommand::AimUp); }
+        if self.down {
+            commands.push(PlayerCommand::AimDown);
+            commands.push(PlayerCommand::Crouch);
+        }
+
+        if self.jump && !previous.jump { commands.push(PlayerCommand::Jump); }
+        if !self.jump && previous.jump { commands.push(PlayerCommand::StopJump); }
+
+        if self.dash && !previous.dash { commands.push(PlayerCommand::Dash); }
+        if self.down && !previous.down && !self.left && !self.right { commands.push(PlayerCommand::Drop); }
+
+        commands
+    }
+}
+
+
+impl std::fmt::Display for HeldInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut keys = Vec::new();
+
+        if self.left { keys.push("left"); }
+        if self.right { keys.push("right"); }
+        if self.up { keys.push("up"); }
+        if self.down { keys.push("down"); }
+        if self.jump { keys.push("jump"); }
+        if self.dash { keys.push("dash"); }
+
+        if keys.is_empty() {
+            write!(f, "idle")
+        } else {
+            write!(f, "{}", keys.join("+"))
+        }
+    }
+}
+
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // The heap pops the greatest element, so the closest, and then oldest, candidate is the greatest
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        other.priority.partial_cmp(&self.priority).unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tile_map::Tile;
+
+    /// A floor from x = -4 to 8, with the player starting on its left
+    fn floor() -> TileMap {
+        let mut tile_map = TileMap::new(::TILE_SIZE);
+        for x in -4..9 {
+            tile_map.add_tile(Vector2i::new(x, 0), Tile::Square);
+        }
+        tile_map.set_player_start(Vector2i::new(-2, -1));
+        tile_map
+    }
+
+    #[test]
+    fn walks_to_a_nearby_exit() {
+        let mut tile_map = floor();
+        tile_map.set_exit(Some(Vector2i::new(4, -1)));
+
+        let report = Solver::new(&tile_map, MovementConfig::new(), 20_000).with_stop_at_exit(true).solve();
+        let witness = report.witness.expect("the exit should be reachable");
+
+        // Holding the inputs of the witness has to take the player to the exit
+        let mut simulation = Simulation::new(tile_map);
+        let mut previous = HeldInput::idle();
+        let mut reached_exit = false;
+
+        for (input, ticks) in witness {
+            for _ in 0..ticks {
+                simulation.step(&input.commands(previous));
+                previous = input;
+
+                reached_exit |= simulation.is_at_exit(simulation.get_state());
+            }
+        }
+
+        assert!(reached_exit);
+    }
+
+    #[test]
+    fn gives_up_on_an_unreachable_exit() {
+        // The player is shut in a box, with the exit outside it
+        let mut tile_map = floor();
+        for y in -4..0 {
+            tile_map.add_tile(Vector2i::new(-4, y), Tile::Square);
+            tile_map.add_tile(Vector2i::new(1, y), Tile::Square);
+        }
+        for x in -4..2 {
+            tile_map.add_tile(Vector2i::new(x, -4), Tile::Square);
+        }
+        tile_map.set_exit(Some(Vector2i::new(4, -1)));
+
+        let report = Solver::new(&tile_map, MovementConfig::new(), 500).with_stop_at_exit(true).solve();
+
+        assert!(report.witness.is_none());
+        assert_eq!(report.expanded, 500);
+        assert!(report.visited_cells.iter().all(|cell| cell.x > -4 && cell.x < 1 && cell.y > -4 && cell.y < 0));
+    }
+
+    #[test]
+    fn inputs_press_and_release_like_the_controls() {
+        let idle = HeldInput::idle();
+        let jump = HeldInput { jump: true, .. idle };
+
+        assert!(jump.commands(idle).contains(&PlayerCommand::Jump));
+        assert!(!jump.commands(jump).contains(&PlayerCommand::Jump));
+        assert!(idle.commands(jump).contains(&PlayerCommand::StopJump));
+
+        let drop = HeldInput { down: true, .. idle };
+        let slide = HeldInput { down: true, right: true, .. idle };
+        assert!(drop.commands(idle).contains(&PlayerCommand::Drop));
+        assert!(!slide.commands(idle).contains(&PlayerCommand::Drop));
+        assert!(slide.commands(idle).contains(&PlayerCommand::Slide));
+    }
+}
@@ -10,12 +10,18 @@ use rax::collision::*;
 use rax::Renderer;
 
 use player::Player;
+use animation::AnimationSet;
 
+#[derive(Clone)]
 pub struct TileMap {
     tiles: HashMap<Vector2i, (Tile, ConvexHull)>,
     tile_size: f64,
 
     player_start: Vector2i,
+    exit: Option<Vector2i>,
+
+    // Tiles containing pickups that refill the player's dashes
+    dash_pickups: Vec<Vector2i>,
 }
 
 
@@ -57,34 +63,41 @@ impl TileMap {
             tile_size,
 
             player_start: Vector2i::new(0, 0),
+            exit: None,
+
+            dash_pickups: Vec::new(),
         }
     }
 
 
-    /// Open a tile map from disk
-    pub fn open<P: AsRef<Path>>(path: P, tile_size: f64) -> Option<TileMap> {
-        if let Ok(mut file) = File::open(path) {
-            use std::io::Read;
-
-            let mut string = String::new();
-            if file.read_to_string(&mut string).is_err() {
-                return None;
-            }
+    /// Open a tile map from disk, returns a message describing what went wrong if it could not
+    /// be read or parsed
+    pub fn open<P: AsRef<Path>>(path: P, tile_size: f64) -> Result<TileMap, String> {
+        use std::io::Read;
 
-            return TileMap::from_str(&string, tile_size);
-        } else {
-            None
+        let mut string = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut string)) {
+            Ok(_) => TileMap::from_str(&string, tile_size),
+            Err(e) => Err(format!("failed to read file: {}", e)),
         }
     }
 
 
     /// Save a tile map to disk
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
-        match std::fs::OpenOptions::new().create(true).write(true).open(path) {
+        match std::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path) {
             Ok(mut file) => {
                 use std::io::Write;
                 let mut text = format!("PLAYER_START {} {}", self.player_start.x, self.player_start.y);
 
+                if let Some(exit) = self.exit {
+                    text.push_str(&format!("\nEXIT {} {}", exit.x, exit.y));
+                }
+
+                for pickup in self.dash_pickups.iter() {
+                    text.push_str(&format!("\nDASH_PICKUP {} {}", pickup.x, pickup.y));
+                }
+
                 let mut tiles: HashMap<Tile, Vec<Vector2i>> = HashMap::new();
                 for (position, tile) in self.tiles.iter() {
                     if let Some(ref mut positions) = tiles.get_mut(&tile.0) {
@@ -114,63 +127,75 @@ impl TileMap {
     }
 
 
-    /// Parses a string describing a tile map
-    fn from_str(text: &str, tile_size: f64) -> Option<TileMap> {
+    /// Parses a string describing a tile map, returns a message with the number of the line that
+    /// could not be parsed on failure
+    pub fn from_str(text: &str, tile_size: f64) -> Result<TileMap, String> {
         let mut tile_map = TileMap {
             tiles: HashMap::new(),
             tile_size,
             player_start: Vector2i::new(0, 0),
+            exit: None,
+
+            dash_pickups: Vec::new(),
         };
 
-        let lines = text.lines().map(|l| { l.split_whitespace() });
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let mut line = line.split_whitespace();
 
-        for mut line in lines {
             if let Some(word) = line.next() {
                 match word {
                     // Sets the start location of the player
                     "PLAYER_START" => {
-                        if let Some(x) = line.next() {
-                            if let Some(y) = line.next() {
-                                tile_map.player_start.x = x.parse().unwrap();
-                                tile_map.player_start.y = y.parse().unwrap();
-                                continue;
-                            }
-                        }
+                        tile_map.player_start = parse_position(word, number, line.next(), line.next())?;
+                    }
+
+
+                    // Sets the location of the exit
+                    "EXIT" => {
+                        tile_map.exit = Some(parse_position(word, number, line.next(), line.next())?);
+                    }
+
 
-                        println!("PLAYER_START: invalid number of arguments!");
-                        return None;
+                    // Adds a dash pickup
+                    "DASH_PICKUP" => {
+                        let pickup = parse_position(word, number, line.next(), line.next())?;
+                        tile_map.dash_pickups.push(pickup);
                     }
 
 
                     // Adds new tiles to the map
                     "TILE" => {
                         if let Some(id) = line.next() {
-                            let tile = Tile::from(id.trim_matches('\"'));
-                            while let Some(coordinate) = line.next() {
+                            let name = id.trim_matches('\"');
+                            let tile = if let Some(tile) = Tile::from_name(name) {
+                                tile
+                            } else {
+                                println!("Skipping unknown tile '{}'", name);
+                                continue;
+                            };
+
+                            for coordinate in line {
                                 let mut numbers = coordinate.split(':');
+                                let position = parse_position(word, number, numbers.next(), numbers.next())?;
 
-                                if let Some(x) = numbers.next() {
-                                    if let Some(y) = numbers.next() {
-                                        tile_map.add_tile(Vector2i::new(x.parse().unwrap(), y.parse().unwrap()), tile);
-                                        continue;
-                                    }
+                                if numbers.next().is_some() {
+                                    return Err(format!("line {}: TILE: invalid coordinate '{}'", number, coordinate));
                                 }
 
-                                println!("Invalid coordinate format: '{}'", coordinate);
-                                return None;
+                                tile_map.add_tile(position, tile);
                             }
                         }
                     }
 
                     word => {
-                        println!("Failed to load tile map: invalid command '{}'", word);
-                        return None;
+                        return Err(format!("line {}: invalid command '{}'", number, word));
                     }
                 }
             }
         }
 
-        Some(tile_map)
+        Ok(tile_map)
     }
 
 
@@ -180,9 +205,82 @@ impl TileMap {
     }
 
 
+    /// Returns the tile the player starts in
+    pub fn get_player_start(&self) -> Vector2i {
+        self.player_start
+    }
+
+
+    /// Moves the player's start to a tile
+    pub fn set_player_start(&mut self, pos: Vector2i) {
+        self.player_start = pos;
+    }
+
+
+    /// Returns the tile containing the exit, if the map has one
+    pub fn get_exit(&self) -> Option<Vector2i> {
+        self.exit
+    }
+
+
+    /// Moves or removes the exit
+    pub fn set_exit(&mut self, exit: Option<Vector2i>) {
+        self.exit = exit;
+    }
+
+
+    /// Returns the tiles containing dash pickups
+    pub fn get_dash_pickups(&self) -> &[Vector2i] {
+        &self.dash_pickups
+    }
+
+
+    /// Adds a dash pickup, unless there already is one in the tile
+    pub fn add_dash_pickup(&mut self, pos: Vector2i) {
+        if !self.dash_pickups.contains(&pos) {
+            self.dash_pickups.push(pos);
+        }
+    }
+
+
+    /// Removes the dash pickup in a tile
+    pub fn remove_dash_pickup(&mut self, pos: Vector2i) {
+        self.dash_pickups.retain(|pickup| *pickup != pos);
+    }
+
+
+    /// Returns the tile containing a point in the world
+    pub fn world_to_tile(&self, world: Vector2) -> Vector2i {
+        Vector2i {
+            x: (world.x / self.tile_size).floor() as i64,
+            y: (world.y / self.tile_size).floor() as i64,
+        }
+    }
+
+
+    /// Returns the tile at a position, if any
+    pub fn get_tile(&self, pos: Vector2i) -> Option<Tile> {
+        self.tiles.get(&pos).map(|&(tile, _)| tile)
+    }
+
+
+    /// Returns every tile in the map, sorted by row and then column
+    pub fn get_tiles(&self) -> Vec<(Vector2i, Tile)> {
+        let mut tiles: Vec<(Vector2i, Tile)> = self.tiles.iter().map(|(pos, &(tile, _))| (*pos, tile)).collect();
+        tiles.sort_by_key(|&(pos, _)| (pos.y, pos.x));
+        tiles
+    }
+
+
+    /// Returns true if the map has no tiles
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+
     /// Returns a new player located in this map
-    pub fn spawn_player(&self) -> Player {
-        Player::new(self.tile_size * Vector2::from(self.player_start) + Vector2::new(self.tile_size / 2.0, self.tile_size / 2.0))
+    pub fn spawn_player(&self, animations: &AnimationSet) -> Player {
+        Player::new(self.tile_size * Vector2::from(self.player_start) + Vector2::new(self.tile_size / 2.0, self.tile_size / 2.0), animations)
     }
 
 
@@ -252,6 +350,15 @@ impl TileMap {
 
     /// Renders the entire map
     pub fn draw(&self, renderer: &mut Renderer) {
+        // Exit
+        if let Some(exit) = self.exit {
+            let left = exit.x as f64 * self.tile_size;
+            let top = exit.y as f64 * self.tile_size;
+
+            renderer.color = [0.0, 1.0, 0.0, 0.3];
+            renderer.fill_rectangle(left, left + self.tile_size, top, top + self.tile_size);
+        }
+
         // Tiles
         for (_, &(_, ref obstacle)) in self.tiles.iter() {
             renderer.color = [1.0, 0.0, 0.0, 0.2];
@@ -268,6 +375,20 @@ impl TileMap {
     }
 
 
+    /// Renders the dash pickup in a tile, in the renderer's current color
+    pub fn draw_dash_pickup(&self, renderer: &mut Renderer, pos: Vector2i) {
+        let center = (Vector2::from(pos) + Vector2::new(0.5, 0.5)) * self.tile_size;
+        let radius = self.tile_size / 4.0;
+
+        renderer.fill_convex(&[
+            center + Vector2::new(0.0, -radius),
+            center + Vector2::new(radius, 0.0),
+            center + Vector2::new(0.0, radius),
+            center + Vector2::new(-radius, 0.0),
+        ]);
+    }
+
+
     /// Renders shadows casted from a singular point
     pub fn draw_shadows(&self, renderer: &mut Renderer, center: Vector2) {
         for (_, &(_, ref obstacle)) in self.tiles.iter() {
@@ -293,28 +414,83 @@ impl<C> Collide<C> for TileMap
     where C: Collide<ConvexHull>
 {
     fn overlap(&self, other: &C) -> Option<(f64, Vector2)> {
-        let smallest = std::f64::INFINITY;
-        let mut best = None;
+        let mut best: Option<(f64, Vector2i, Vector2)> = None;
 
         let bounding_box = other.bounding_box();
 
-        for (_, &(_, ref obstacle)) in self.tiles.iter() {
+        for (pos, &(_, ref obstacle)) in self.tiles.iter() {
             // Broad phase
             if bounding_box.intersects(&obstacle.bounding_box()) {
                 // Narrow phase
                 if let Some((overlap, resolve)) = other.overlap(obstacle) {
-                    if overlap < smallest {
-                        best = Some(-resolve);
+                    // Ties are broken by position, so that the result does not depend on the
+                    // iteration order of the map
+                    let better = match best {
+                        Some((smallest, best_pos, _)) => {
+                            overlap < smallest ||
+                                (overlap == smallest && (pos.y, pos.x) < (best_pos.y, best_pos.x))
+                        }
+                        None => true,
+                    };
+
+                    if better {
+                        best = Some((overlap, *pos, -resolve));
+                    }
+                }
+            }
+        }
+
+        best.map(|(overlap, _, resolve)| (overlap, resolve))
+    }
+
+
+    fn sweep(&self, other: &C, motion: Vector2) -> Option<(f64, Vector2)> {
+        let mut best: Option<(f64, Vector2)> = None;
+
+        // Only the tiles the other shape passes over during the motion are checked, in order of
+        // position so that ties are always broken the same way
+        let bounding_box = other.bounding_box();
+        let min = self.world_to_tile(Vector2::new(bounding_box.left + motion.x.min(0.0), bounding_box.top + motion.y.min(0.0)));
+        let max = self.world_to_tile(Vector2::new(bounding_box.right + motion.x.max(0.0), bounding_box.bottom + motion.y.max(0.0)));
+
+        for y in min.y..max.y + 1 {
+            for x in min.x..max.x + 1 {
+                if let Some(&(_, ref obstacle)) = self.tiles.get(&Vector2i::new(x, y)) {
+                    // The tile moves towards the other shape instead
+                    if let Some((time, normal)) = other.sweep(obstacle, -motion) {
+                        if best.map_or(true, |(earliest, _)| time < earliest) {
+                            best = Some((time, -normal));
+                        }
                     }
                 }
             }
         }
 
-        if let Some(resolve) = best {
-            Some((smallest, resolve))
-        } else {
-            None
+        best
+    }
+
+
+    fn raycast(&self, origin: Vector2, direction: Vector2) -> Option<(f64, Vector2)> {
+        let mut best: Option<(f64, Vector2)> = None;
+
+        // Only the tiles in the box around the ray are checked
+        let end = origin + direction;
+        let min = self.world_to_tile(Vector2::new(origin.x.min(end.x), origin.y.min(end.y)));
+        let max = self.world_to_tile(Vector2::new(origin.x.max(end.x), origin.y.max(end.y)));
+
+        for y in min.y..max.y + 1 {
+            for x in min.x..max.x + 1 {
+                if let Some(&(_, ref obstacle)) = self.tiles.get(&Vector2i::new(x, y)) {
+                    if let Some((time, normal)) = obstacle.raycast(origin, direction) {
+                        if best.map_or(true, |(earliest, _)| time < earliest) {
+                            best = Some((time, normal));
+                        }
+                    }
+                }
+            }
         }
+
+        best
     }
 }
 
@@ -528,11 +704,29 @@ impl Tile {
             }
         }
     }
+
+
+    /// Returns the top of the side of the tile that is hit when moving in a direction, relative to
+    /// the tile, if that side has a vertical edge that can be held on to
+    pub fn get_ledge(&self, size: f64, incoming_direction: Direction) -> Option<Vector2> {
+        let side = match incoming_direction {
+            Direction::Left => size,
+            Direction::Right => 0.0,
+            Direction::Up | Direction::Down => return None,
+        };
+
+        let hull = self.get_convex_hull(size);
+        let mut corners = hull.get_points().iter().filter(|p| p.x == side);
+
+        let first = if let Some(first) = corners.next() { *first } else { return None };
+        corners.next().map(|second| if second.y < first.y { *second } else { first })
+    }
 }
 
-impl<'a> From<&'a str> for Tile {
-    fn from(id: &'a str) -> Self {
-        match id as &str {
+impl Tile {
+    /// Returns the tile with a name, or None if the name is unknown
+    pub fn from_name(id: &str) -> Option<Tile> {
+        Some(match id {
             "Square" => Tile::Square,
 
             "WedgeUpLeft" => Tile::WedgeUpLeft,
@@ -550,7 +744,16 @@ impl<'a> From<&'a str> for Tile {
             "SlantedWedgeDownLeft" => Tile::SlantedWedgeDownLeft,
             "SlantedWedgeDownRight" => Tile::SlantedWedgeDownRight,
 
-            id => panic!("Did not recognize '{}' as a tile name", id)
+            _ => return None
+        })
+    }
+}
+
+impl<'a> From<&'a str> for Tile {
+    fn from(id: &'a str) -> Self {
+        match Tile::from_name(id) {
+            Some(tile) => tile,
+            None => panic!("Did not recognize '{}' as a tile name", id)
         }
     }
 }
@@ -580,7 +783,7 @@ impl std::fmt::Display for Tile {
 
 
 impl Direction {
-    fn all() -> [Direction; 4] {
+    pub fn all() -> [Direction; 4] {
         [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
     }
 
@@ -602,3 +805,57 @@ impl Direction {
         }
     }
 }
+
+
+/// Parses the coordinates of a tile, given to a command on a line of a tile map file
+fn parse_position(command: &str, line: usize, x: Option<&str>, y: Option<&str>) -> Result<Vector2i, String> {
+    let (x, y) = match (x, y) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(format!("line {}: {}: expected an x and y coordinate", line, command)),
+    };
+
+    match (x.parse(), y.parse()) {
+        (Ok(x), Ok(y)) => Ok(Vector2i::new(x, y)),
+        _ => Err(format!("line {}: {}: invalid coordinate '{} {}'", line, command, x, y)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_level() {
+        let text = "PLAYER_START 1 -2\nEXIT 5 -1\n\nDASH_PICKUP 3 -3\nTILE \"Square\" 0:0 1:0\nTILE WedgeUpLeft 2:0";
+        let tile_map = TileMap::from_str(text, 48.0).unwrap();
+
+        assert!(tile_map.get_player_start() == Vector2i::new(1, -2));
+        assert!(tile_map.get_exit() == Some(Vector2i::new(5, -1)));
+        assert!(tile_map.get_dash_pickups() == [Vector2i::new(3, -3)]);
+
+        assert!(tile_map.get_tile(Vector2i::new(0, 0)) == Some(Tile::Square));
+        assert!(tile_map.get_tile(Vector2i::new(1, 0)) == Some(Tile::Square));
+        assert!(tile_map.get_tile(Vector2i::new(2, 0)) == Some(Tile::WedgeUpLeft));
+        assert!(tile_map.get_tile(Vector2i::new(3, 0)).is_none());
+    }
+
+    #[test]
+    fn reports_the_line_of_an_error() {
+        let error = |text: &str| TileMap::from_str(text, 48.0).err().unwrap();
+
+        assert_eq!(error("EXIT 1 1\nSPAWN 2 2"), "line 2: invalid command 'SPAWN'");
+        assert_eq!(error("PLAYER_START 1"), "line 1: PLAYER_START: expected an x and y coordinate");
+        assert_eq!(error("\n\nEXIT a 1"), "line 3: EXIT: invalid coordinate 'a 1'");
+        assert_eq!(error("TILE Square 0:0 1"), "line 1: TILE: expected an x and y coordinate");
+        assert_eq!(error("TILE Square 0:0:0"), "line 1: TILE: invalid coordinate '0:0:0'");
+    }
+
+    #[test]
+    fn skips_unknown_tiles() {
+        let tile_map = TileMap::from_str("TILE Lava 0:0\nTILE Square 1:0", 48.0).unwrap();
+
+        assert!(tile_map.get_tile(Vector2i::new(0, 0)).is_none());
+        assert!(tile_map.get_tile(Vector2i::new(1, 0)) == Some(Tile::Square));
+    }
+}
@@ -0,0 +1,138 @@
+use trap::Vector2;
+use rax::collision::*;
+
+use player::RagDoll;
+
+
+/// Pairs of points, as ordered by RagDoll::get_points, that are kept at a fixed distance
+const STICKS: [(usize, usize); 9] = [
+    (0, 1),
+
+    (1, 2), (2, 4),
+    (1, 3), (3, 5),
+
+    (0, 6), (6, 8),
+    (0, 7), (7, 9),
+];
+
+/// Number of times the constraints are solved per step, more is stiffer
+const ITERATIONS: u32 = 4;
+
+/// Size of the box each point collides as
+const POINT_RADIUS: f64 = 1.5;
+
+/// Fraction of the velocity lost per second while flying through the air
+const DRAG: f64 = 0.5;
+
+/// Fraction of the velocity along a surface lost per second while a point touches it
+const FRICTION: f64 = 10.0;
+
+
+/// A rag doll that is pulled around by gravity and bounces off obstacles, instead of following a
+/// pose
+#[derive(Clone)]
+pub struct VerletRagDoll {
+    points: [Vector2; RagDoll::POINT_COUNT],
+
+    // Where the points were during the previous step, the difference is their velocity
+    previous: [Vector2; RagDoll::POINT_COUNT],
+
+    lengths: [f64; 9],
+}
+
+
+impl VerletRagDoll {
+    /// Create a rag doll in a pose, moving at a velocity in pixels per second
+    pub fn new(pose: &RagDoll, velocity: Vector2, dt: f64) -> VerletRagDoll {
+        let points = pose.get_points();
+
+        let mut previous = points;
+        for point in previous.iter_mut() {
+            *point = *point - velocity * dt;
+        }
+
+        let mut lengths = [0.0; 9];
+        for (length, &(a, b)) in lengths.iter_mut().zip(STICKS.iter()) {
+            *length = points[a].distance(points[b]);
+        }
+
+        VerletRagDoll {
+            points,
+            previous,
+            lengths,
+        }
+    }
+
+
+    /// Advance the rag doll by a step, in seconds
+    pub fn step(&mut self, dt: f64, gravity: f64, obstacles: &[&Collide<ConvexHull>]) {
+        for i in 0..RagDoll::POINT_COUNT {
+            let velocity = self.points[i] - self.previous[i];
+
+            self.previous[i] = self.points[i];
+            self.points[i] += velocity * (1.0 - DRAG * dt) + Vector2::new(0.0, gravity * dt * dt);
+        }
+
+        let mut contacts = [None; RagDoll::POINT_COUNT];
+        for _ in 0..ITERATIONS {
+            self.satisfy_sticks();
+            self.collide(obstacles, &mut contacts);
+        }
+
+        // Points stop moving into the surfaces they touch, and slow down along them
+        for i in 0..RagDoll::POINT_COUNT {
+            if let Some(normal) = contacts[i] {
+                let velocity = self.points[i] - self.previous[i];
+                let along = velocity - normal * velocity.dot(normal);
+
+                self.previous[i] = self.points[i] - along * (1.0 - FRICTION * dt).max(0.0);
+            }
+        }
+    }
+
+
+    /// Moves the ends of every stick towards, or away from, each other until they are as far apart
+    /// as they were at the start
+    fn satisfy_sticks(&mut self) {
+        for (&(a, b), &length) in STICKS.iter().zip(self.lengths.iter()) {
+            let delta = self.points[b] - self.points[a];
+            let distance = delta.len();
+
+            if distance > 0.0 {
+                let correction = delta * ((distance - length) / distance / 2.0);
+
+                self.points[a] += correction;
+                self.points[b] += -correction;
+            }
+        }
+    }
+
+
+    /// Pushes points out of obstacles, remembering the normal of the surface each point was pushed
+    /// out of
+    fn collide(&mut self, obstacles: &[&Collide<ConvexHull>], contacts: &mut [Option<Vector2>; RagDoll::POINT_COUNT]) {
+        for i in 0..RagDoll::POINT_COUNT {
+            let point = self.points[i];
+            let mut probe = ConvexHull::from_points(&[
+                Vector2::new(point.x - POINT_RADIUS, point.y - POINT_RADIUS),
+                Vector2::new(point.x + POINT_RADIUS, point.y - POINT_RADIUS),
+                Vector2::new(point.x + POINT_RADIUS, point.y + POINT_RADIUS),
+                Vector2::new(point.x - POINT_RADIUS, point.y + POINT_RADIUS),
+            ]);
+
+            for obstacle in obstacles.iter() {
+                if let Some((_, resolve)) = obstacle.overlap(&probe) {
+                    probe.translate(-resolve);
+                    self.points[i] += -resolve;
+                    contacts[i] = Some(-resolve.norm());
+                }
+            }
+        }
+    }
+
+
+    /// Returns the current pose
+    pub fn get_pose(&self) -> RagDoll {
+        RagDoll::from_points(&self.points)
+    }
+}